@@ -9,6 +9,8 @@ use anyhow::{anyhow, Result};
 use regex::Regex;
 use structopt::lazy_static::lazy_static;
 
+use crate::template::is_valid_template;
+
 /// A `Result` used to represents an alias, valid or invalid. A valid alias is represented
 /// as `Result::Ok<&str>`, where the **&str** is the alias (e.g. "gandalf.local"). An invalid
 /// alias is represented as `Result::Err<&str>`, where the **&str** is the invalid alias.
@@ -58,6 +60,7 @@ pub fn is_valid_alias(alias: &str) -> bool {
 ///
 /// let a1 = new_alias("a1.local"); // Ok("a1.local")
 /// let a2 = new_alias("a*.local"); // Err("a*.local")
+/// let a3 = new_alias("{hostname}-db.local"); // Err("{hostname}-db.local")
 /// ```
 pub fn new_alias(alias: &str) -> Alias<'_> {
     match is_valid_alias(alias) {
@@ -66,6 +69,26 @@ pub fn new_alias(alias: &str) -> Alias<'_> {
     }
 }
 
+/// Creates a new `Alias` from an alias as written in an aliases file: valid alias templates
+/// (e.g., `{hostname}-db.local`) are also `Ok`. What a template expands to is validated by
+/// `AliasesFile::expand`.
+///
+/// # Examples
+///
+/// ```
+/// use avahi_aliases::new_alias_or_template;
+///
+/// let a1 = new_alias_or_template("a1.local"); // Ok("a1.local")
+/// let a2 = new_alias_or_template("{hostname}-db.local"); // Ok("{hostname}-db.local")
+/// let a3 = new_alias_or_template("{hostname-db.local"); // Err("{hostname-db.local")
+/// ```
+pub fn new_alias_or_template(alias: &str) -> Alias<'_> {
+    match is_valid_alias(alias) || is_valid_template(alias) {
+        true => Ok(alias),
+        false => Err(alias),
+    }
+}
+
 /// Ensure all aliases in a collection are valid.
 ///
 /// If an invalid alias is found, returns `Err(ErrorWrapper::InvalidAliasError)` for the
@@ -96,13 +119,27 @@ where
     }
 }
 
+/// Ensure all aliases in a collection are valid aliases or valid alias templates (e.g., for
+/// aliases added to a file).
+pub fn validate_aliases_or_templates<T>(aliases: &[T]) -> Result<()>
+where
+    T: AsRef<str>, {
+    match aliases.iter().find(|a| new_alias_or_template(a.as_ref()).is_err()) {
+        Some(invalid_alias) => Err(anyhow!(r#"invalid alias: "{}""#, invalid_alias.as_ref())),
+        None => Ok(()),
+    }
+}
+
 //**********************************************************************************************
 // Unit tests
 //**********************************************************************************************
 
 #[cfg(test)]
 mod tests {
-    use super::{is_valid_alias, new_alias, validate_aliases};
+    use super::{
+        is_valid_alias, new_alias, new_alias_or_template, validate_aliases,
+        validate_aliases_or_templates,
+    };
 
     static VALID_ALIASES: [&str; 4] = ["a.local", "xyzzy.local", "b0.local", "a-z.local"];
     static INVALID_ALIASES: [&str; 5] =
@@ -149,4 +186,17 @@ mod tests {
         let r = validate_aliases(&INVALID_ALIASES);
         assert!(r.is_err())
     }
+
+    #[test]
+    fn templates_are_valid_but_not_valid_aliases() {
+        for template in ["{hostname}.local", "{hostname}-db.local"] {
+            assert!(!is_valid_alias(template));
+            assert!(new_alias(template).is_err());
+            assert!(validate_aliases(&[template]).is_err());
+            assert_eq!(new_alias_or_template(template).unwrap(), template);
+            assert!(validate_aliases_or_templates(&[template, "a.local"]).is_ok());
+        }
+        assert!(new_alias_or_template("{hostname.local").is_err());
+        assert!(validate_aliases_or_templates(&["{hostname.local"]).is_err());
+    }
 }
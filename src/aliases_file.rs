@@ -2,13 +2,38 @@
 
 #![warn(clippy::all)]
 
-use std::io::{BufWriter, Read, Write};
+use std::collections::HashSet;
+use std::io::{self, Read, Write};
+use std::os::unix::fs::{MetadataExt, OpenOptionsExt, PermissionsExt};
+use std::path::Path;
 use std::{self, fs, str};
 
 use anyhow::{anyhow, Context, Result};
+use time::OffsetDateTime;
 
 use crate::alias::{self, Alias};
-use crate::Line;
+use crate::line::is_valid_attribute;
+use crate::{diff, Line};
+use crate::format::format_lines;
+use crate::history::History;
+use crate::template::{self, is_template, Variables};
+
+/// The content of a new aliases file (a header documenting the format)
+pub const FILE_TEMPLATE: &str = include_str!("../etc/avahi-aliases");
+
+/// The mode of a new aliases file (`rw-r--r--`)
+const FILE_MODE: u32 = 0o644;
+
+/// How changes to an aliases file are shown and recorded (see `AliasesFile::with_preview`)
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Preview {
+    /// Show the changes (as a unified diff) instead of changing the file
+    pub dry_run: bool, // cov(skip)
+    /// Show the changes (as a unified diff) when the file is changed
+    pub diff: bool, // cov(skip)
+    /// Save the previous content of the file before changing it
+    pub history: Option<History>,
+}
 
 /// An Avahi aliases file.
 ///
@@ -17,20 +42,154 @@ use crate::Line;
 /// and remove aliases.
 #[derive(Debug)]
 pub struct AliasesFile {
-    file_name: String, // cov(skip)
-    lines: Vec<Line>,  // cov(skip)
+    file_name: String,         // cov(skip)
+    lines: Vec<Line>,          // cov(skip)
+    host_name: Option<String>, // cov(skip)
+    preview: Preview,          // cov(skip)
+    now: OffsetDateTime,       // cov(skip)
 }
 
 impl<'a> AliasesFile {
+    /// Restrict the aliases to those applying to a host (i.e., lines without host selectors
+    /// or with a host selector matching `host_name`). Without a host name, every line
+    /// applies.
+    pub fn for_host(mut self, host_name: &str) -> Self {
+        self.host_name = Some(host_name.to_owned());
+        self
+    }
+
+    /// Show changes to the file as unified diffs on standard output, and (if
+    /// `preview.dry_run`) leave the file unchanged. With `preview.history`, the previous
+    /// content is saved before each change.
+    pub fn with_preview(mut self, preview: Preview) -> Self {
+        self.preview = preview;
+        self
+    }
+
+    /// Set the time used to decide whether aliases have expired (the default is the time the
+    /// file was loaded).
+    pub fn at_time(mut self, now: OffsetDateTime) -> Self {
+        self.now = now;
+        self
+    }
+
+    /// Expand the alias templates (e.g., `{hostname}-grafana.local`) using `variables`.
+    /// Templates that cannot be expanded, or expand to invalid aliases, become invalid aliases.
+    /// Note: the file content is not changed; rewrites preserve the templates.
+    pub fn expand(mut self, variables: &Variables) -> Self {
+        self.lines.iter_mut().for_each(|line| line.expand(variables));
+        self
+    }
+
+    /// Expand the alias templates like `expand`, except those using variables missing from
+    /// `variables` (e.g., variables defined on the daemon command line): they are left
+    /// unexpanded (see `unexpanded_templates`) rather than becoming invalid aliases.
+    pub fn expand_defined(mut self, variables: &Variables) -> Self {
+        self.lines
+            .iter_mut()
+            .filter(|line| line.template().is_some_and(|t| template::is_defined(t, variables)))
+            .for_each(|line| line.expand(variables));
+        self
+    }
+
+    /// Return the valid alias templates that apply to the host and are not expanded (see
+    /// `expand_defined`).
+    pub fn unexpanded_templates(&self) -> Vec<&str> {
+        self.all_aliases()
+            .into_iter()
+            .filter_map(|a| a.ok())
+            .filter(|a| is_template(a))
+            .collect()
+    }
+
+    /// Returns `true` if any line has an alias template.
+    pub fn has_templates(&self) -> bool {
+        self.lines.iter().any(|line| line.template().is_some())
+    }
+
+    /// Return the name of the file.
+    pub fn file_name(&self) -> &str { &self.file_name }
+
+    /// Return the host name set by `for_host`.
+    pub fn host_name(&self) -> Option<&str> { self.host_name.as_deref() }
+
+    /// Returns `true` if any line has a host selector.
+    pub fn has_selectors(&self) -> bool {
+        self.lines.iter().any(|line| !line.selectors().is_empty())
+    }
+
     /// Return a vector containing the aliases.
     /// Note: this function returns both valid and invalid aliases.
     pub fn all_aliases(&self) -> Vec<Alias<'_>> {
-        self.lines.iter().filter_map(|line| line.alias()).collect()
+        self.group_lines()
+            .filter(|(header, line)| self.applies_to_host(*header, line))
+            .filter_map(|(_, line)| line.alias())
+            .collect()
     }
 
-    /// Return a vector of valid aliases.
+    /// Return a vector of valid, enabled aliases (i.e., the aliases to publish).
+    /// Note: aliases in disabled groups are not enabled.
     pub fn aliases(&self) -> Vec<&str> {
-        self.lines.iter().filter_map(|line| line.alias().map(|a| a.ok()).flatten()).collect()
+        self.group_lines()
+            .filter(|(header, line)| self.is_active(*header, line))
+            .filter_map(|(_, line)| line.alias().and_then(|a| a.ok()))
+            .collect()
+    }
+
+    /// Return the valid, enabled aliases of each enabled group, in file order. Aliases
+    /// preceding the first group header belong to the default group (`None`). Sections
+    /// with the same group name are merged; groups without enabled aliases are omitted.
+    pub fn group_aliases(&self) -> Vec<(Option<&str>, Vec<&str>)> {
+        let mut groups = Vec::<(Option<&str>, Vec<&str>)>::new();
+        for (header, line) in self.group_lines().filter(|(h, l)| self.is_active(*h, l)) {
+            if let Some(Ok(alias)) = line.alias() {
+                let group = header.and_then(Line::group);
+                match groups.iter_mut().find(|(g, _)| *g == group) {
+                    Some((_, aliases)) => aliases.push(alias),
+                    None => groups.push((group, vec![alias])),
+                }
+            }
+        }
+        groups
+    }
+
+    /// Return the group names, in order of first appearance, and whether each is enabled.
+    pub fn groups(&self) -> Vec<(&str, bool)> {
+        let mut groups = Vec::<(&str, bool)>::new();
+        for line in self.lines.iter() {
+            if let Some(group) = line.group() {
+                if !groups.iter().any(|(g, _)| *g == group) {
+                    groups.push((group, !line.is_disabled()));
+                }
+            }
+        }
+        groups
+    }
+
+    /// Return a vector of valid, disabled aliases.
+    pub fn disabled_aliases(&self) -> Vec<&str> {
+        self.group_lines()
+            .filter(|(header, line)| line.is_disabled() && self.applies_to_host(*header, line))
+            .filter_map(|(_, line)| line.alias().and_then(|a| a.ok()))
+            .collect()
+    }
+
+    /// Return the valid aliases that have expired (see `at_time`).
+    pub fn expired_aliases(&self) -> Vec<&str> {
+        self.group_lines()
+            .filter(|(header, line)| {
+                line.is_expired(self.now) && self.applies_to_host(*header, line)
+            })
+            .filter_map(|(_, line)| line.alias().and_then(|a| a.ok()))
+            .collect()
+    }
+
+    /// Return the time the next published alias expires, if any.
+    pub fn next_expiry(&self) -> Option<OffsetDateTime> {
+        self.group_lines()
+            .filter(|(header, line)| self.is_active(*header, line))
+            .filter_map(|(_, line)| line.expires())
+            .min()
     }
 
     /// Return a vector of invalid aliases.
@@ -41,6 +200,9 @@ impl<'a> AliasesFile {
     /// Return the number of aliases
     pub fn alias_count(&self) -> usize { self.aliases().len() }
 
+    /// Return the lines of the file.
+    pub fn lines(&self) -> &[Line] { &self.lines }
+
     pub fn from_file(filename: &str, allow_invalid: bool) -> Result<Self> {
         let mut file = fs::OpenOptions::new()
             .read(true)
@@ -53,6 +215,9 @@ impl<'a> AliasesFile {
         let aliases_file = AliasesFile {
             file_name: filename.to_owned(),
             lines: buf.lines().map(|text| Line::new(text.to_owned())).collect(),
+            host_name: None,
+            preview: Preview::default(),
+            now: OffsetDateTime::now_utc(),
         };
         if allow_invalid || aliases_file.all_aliases_are_valid() {
             Ok(aliases_file)
@@ -65,49 +230,432 @@ impl<'a> AliasesFile {
         }
     }
 
-    pub fn append(&self, aliases: &[&str]) -> Result<()> {
-        alias::validate_aliases(aliases)?;
-        let mut writer = fs::OpenOptions::new()
-            .append(true)
-            .open(&self.file_name)
-            .map(BufWriter::new)
-            .with_context(|| format!(r#"could not open "{}""#, &self.file_name))?;
-        for alias in aliases {
-            writer
-                .write_all(format!("{}\n", alias).as_bytes())
-                .with_context(|| format!(r#"could not write "{}""#, &self.file_name))?;
+    /// An aliases file named `filename` containing `FILE_TEMPLATE`, without creating it
+    /// (e.g., to preview the changes to a new file with a dry run).
+    pub fn from_template(filename: &str) -> Self {
+        AliasesFile {
+            file_name: filename.to_owned(),
+            lines: FILE_TEMPLATE.lines().map(|text| Line::new(text.to_owned())).collect(),
+            host_name: None,
+            preview: Preview::default(),
+            now: OffsetDateTime::now_utc(),
+        }
+    }
+
+    /// Create an aliases file containing `FILE_TEMPLATE`. Fails if the file exists, unless
+    /// `force` (which overwrites it). The file is readable by everyone and, when created by
+    /// root, owned by root.
+    pub fn create(filename: &str, force: bool) -> Result<()> {
+        create_file(filename, FILE_TEMPLATE, force)
+    }
+
+    /// Create the file with the lines (e.g., of `from_template`), like `create`. The change is
+    /// shown and saved in the history like any other (see `with_preview`).
+    pub fn write_new(&self, force: bool) -> Result<()> {
+        let old_text = fs::read_to_string(&self.file_name).ok();
+        let new_lines: Vec<&str> = self.lines.iter().map(Line::text).collect();
+        if self.show_change(old_text.as_deref().unwrap_or_default(), &new_lines) {
+            return Ok(());
+        }
+        let new_text: String = new_lines.iter().map(|line| format!("{}\n", line)).collect();
+        if let (true, Some(old_text)) = (force, &old_text) {
+            self.record_history(old_text, &new_text);
+        }
+        create_file(&self.file_name, &new_text, force)
+    }
+
+    /// Append aliases to the default group (i.e., before the first group header).
+    pub fn append(&self, aliases: &[&str]) -> Result<()> { self.append_to_group(aliases, None) }
+
+    /// Append aliases to the end of a group. The group is created (at the end of the file)
+    /// if it does not exist.
+    pub fn append_to_group(&self, aliases: &[&str], group: Option<&str>) -> Result<()> {
+        self.append_with_comment(aliases, group, None, &[])
+    }
+
+    /// Append aliases to the end of a group (see `append_to_group`) with a comment made of a
+    /// description and attributes (e.g., `owner=team-x`).
+    pub fn append_with_comment(
+        &self, aliases: &[&str], group: Option<&str>, description: Option<&str>,
+        attributes: &[(&str, &str)],
+    ) -> Result<()> {
+        alias::validate_aliases_or_templates(aliases)?;
+        if let Some(&(key, value)) = attributes.iter().find(|(k, v)| !is_valid_attribute(k, v))
+        {
+            return Err(anyhow!(r#"invalid attribute "{}={}""#, key, value));
+        }
+        if description.is_some_and(|d| d.contains(['\n', '\r'])) {
+            return Err(anyhow!("comments must be on one line"));
+        }
+        let new_lines =
+            aliases.iter().map(|alias| Line::with_comment(alias, description, attributes));
+        match self.group_end(group) {
+            Some(end) => self.write_lines(
+                self.lines[..end]
+                    .iter()
+                    .cloned()
+                    .chain(new_lines)
+                    .chain(self.lines[end..].iter().cloned()),
+            ),
+            None => self.write_lines(
+                self.lines
+                    .iter()
+                    .cloned()
+                    .chain(
+                        [String::new(), format!("[{}]", group.unwrap_or_default())]
+                            .map(Line::new),
+                    )
+                    .chain(new_lines),
+            ),
         }
-        Ok(())
     }
 
     pub fn remove(&self, aliases: &[&str], force: bool) -> Result<()> {
         if !force {
             alias::validate_aliases(aliases)?;
         }
-        let mut writer = fs::OpenOptions::new()
-            .truncate(true)
-            .write(true)
-            .open(&self.file_name)
-            .map(BufWriter::new)
-            .with_context(|| format!(r#"could not open "{}""#, &self.file_name))?;
-        let retained_lines = (&self.lines).iter().filter(|line| match line.alias() {
-            Some(Err(_)) => false,                        // Invalid aliases must go!
-            Some(Ok(alias)) => !aliases.contains(&alias), // Remove specified aliases
-            _ => true,                                    // everything else stays
+        let retained_lines = self.group_lines().filter(|(header, line)| match line.alias() {
+            Some(Err(_)) => false, // Invalid aliases must go!
+            Some(Ok(alias)) => {
+                !(aliases.contains(&alias) && self.applies_to_host(*header, line))
+            },
+            _ => true, // everything else stays
         });
-        for line in retained_lines {
-            writer
-                .write_all(format!("{}\n", line.text()).as_bytes())
-                .with_context(|| format!(r#"could not write "{}""#, &self.file_name))?;
+        self.write_lines(retained_lines.map(|(_, line)| line))
+    }
+
+    /// Rename an alias in place: the lines keep their position, disabled marker, host
+    /// selectors, and comment. Fails if `old` is not in the file or `new` already is.
+    pub fn rename(&self, old: &str, new: &str) -> Result<()> {
+        alias::validate_aliases(&[old, new])?;
+        let present: Vec<&str> =
+            self.all_aliases().into_iter().filter_map(|a| a.ok()).collect();
+        if !present.contains(&old) {
+            return Err(anyhow!(r#"alias "{}" not found in "{}""#, old, self.file_name));
         }
-        Ok(())
+        if present.contains(&new) {
+            return Err(anyhow!(r#"alias "{}" already exists in "{}""#, new, self.file_name));
+        }
+        let lines = self.group_lines().map(|(header, line)| {
+            let applies = self.applies_to_host(header, line);
+            let mut line = line.clone();
+            if applies && line.alias() == Some(Ok(old)) {
+                line.set_alias(new);
+            }
+            line
+        });
+        self.write_lines(lines)
+    }
+
+    /// Remove the lines of expired aliases (see `at_time`).
+    pub fn prune(&self) -> Result<()> {
+        let lines = self.group_lines().filter(|(header, line)| {
+            !(line.is_expired(self.now) && self.applies_to_host(*header, line))
+        });
+        self.write_lines(lines.map(|(_, line)| line))
+    }
+
+    /// Return the valid aliases applying to the host that are published, or would be if
+    /// enabled (i.e., not in a disabled group, and not expired).
+    pub fn present_aliases(&self) -> Vec<&str> {
+        self.group_lines()
+            .filter(|(header, line)| self.is_present(*header, line))
+            .filter_map(|(_, line)| line.alias().and_then(|a| a.ok()))
+            .collect()
+    }
+
+    /// Make the aliases applying to the host exactly `aliases`: disabled aliases are enabled,
+    /// extra aliases are removed (unless `keep_extra`), and missing aliases (including those
+    /// in disabled groups, which are moved, and expired ones) are appended to the default
+    /// group. Aliases only in other hosts' lines are appended with a selector for this host.
+    /// Retained lines keep their comments, and alias templates are kept.
+    pub fn sync(&self, aliases: &[&str], keep_extra: bool) -> Result<()> {
+        alias::validate_aliases(aliases)?;
+        let lines = self.group_lines().filter_map(|(header, line)| {
+            let alias = match line.alias() {
+                Some(Ok(alias))
+                    if self.applies_to_host(header, line) && !is_template(alias) =>
+                {
+                    alias
+                },
+                _ => return Some(line.clone()),
+            };
+            let mut line = line.clone();
+            match aliases.contains(&alias) {
+                true if !self.is_present(header, &line) => return None,
+                true => line.set_disabled(false),
+                false if keep_extra => (),
+                false => return None,
+            }
+            Some(line)
+        });
+        let synced = AliasesFile {
+            file_name: self.file_name.clone(),
+            lines: lines.collect(),
+            host_name: self.host_name.clone(),
+            preview: self.preview.clone(),
+            now: self.now,
+        };
+        let present: HashSet<&str> = synced.aliases().into_iter().collect();
+        let other_hosts: HashSet<&str> = synced
+            .group_lines()
+            .filter(|(header, line)| !synced.applies_to_host(*header, line))
+            .filter_map(|(_, line)| line.alias().and_then(|a| a.ok()))
+            .collect();
+        let mut seen = HashSet::<&str>::new();
+        let missing_lines: Vec<Line> = aliases
+            .iter()
+            .filter(|a| !present.contains(*a) && seen.insert(a))
+            .map(|alias| match (&self.host_name, other_hosts.contains(alias)) {
+                (Some(host_name), true) => Line::new(format!("{} @{}", alias, host_name)),
+                _ => Line::new(alias.to_string()),
+            })
+            .collect();
+        let end = synced.group_end(None).unwrap_or_default();
+        synced.write_lines(
+            synced.lines[..end]
+                .iter()
+                .cloned()
+                .chain(missing_lines)
+                .chain(synced.lines[end..].iter().cloned()),
+        )
+    }
+
+    /// Disable the specified aliases. The lines stay in the file (with their comments),
+    /// but the aliases are no longer published.
+    pub fn disable(&self, aliases: &[&str]) -> Result<()> { self.set_disabled(aliases, true) }
+
+    /// Enable the specified (previously disabled) aliases.
+    pub fn enable(&self, aliases: &[&str]) -> Result<()> { self.set_disabled(aliases, false) }
+
+    /// Disable every alias in the specified groups (by disabling the group headers).
+    pub fn disable_groups(&self, groups: &[&str]) -> Result<()> {
+        self.set_groups_disabled(groups, true)
+    }
+
+    /// Enable the specified (previously disabled) groups.
+    pub fn enable_groups(&self, groups: &[&str]) -> Result<()> {
+        self.set_groups_disabled(groups, false)
     }
 
     pub fn all_aliases_are_valid(&self) -> bool { self.invalid_aliases().is_empty() }
 
-    /// Used for testing.
-    #[allow(dead_code)]
-    fn lines(&self) -> &Vec<Line> { &self.lines }
+    /// Return the lines formatted canonically (see the `format` module).
+    pub fn formatted(&self, sort: bool) -> Vec<Line> { format_lines(&self.lines, sort) }
+
+    /// Returns `true` if the file is formatted canonically.
+    pub fn is_formatted(&self, sort: bool) -> bool {
+        self.formatted(sort).iter().map(Line::text).eq(self.lines.iter().map(Line::text))
+    }
+
+    /// Rewrite the file in the canonical format.
+    pub fn format(&self, sort: bool) -> Result<()> {
+        self.write_lines(self.formatted(sort).into_iter())
+    }
+
+    /// Returns `true` if the line (and its group header) applies to the host, neither is
+    /// disabled, and the alias has not expired.
+    fn is_active(&self, header: Option<&Line>, line: &Line) -> bool {
+        !line.is_disabled() && self.is_present(header, line)
+    }
+
+    /// Returns `true` if the line applies to the host and would be active if enabled (i.e., its
+    /// group header is not disabled, and the alias has not expired).
+    fn is_present(&self, header: Option<&Line>, line: &Line) -> bool {
+        !line.is_expired(self.now)
+            && !header.is_some_and(Line::is_disabled)
+            && self.applies_to_host(header, line)
+    }
+
+    /// Returns `true` if the line and its group header apply to the host (see `for_host`).
+    pub fn applies_to_host(&self, header: Option<&Line>, line: &Line) -> bool {
+        match &self.host_name {
+            Some(host_name) => {
+                line.applies_to(host_name) && header.is_none_or(|h| h.applies_to(host_name))
+            },
+            None => true,
+        }
+    }
+
+    /// Return each line paired with the header of the group that contains it.
+    pub fn group_lines(&self) -> impl Iterator<Item = (Option<&Line>, &Line)> {
+        self.lines.iter().scan(None, |header, line| {
+            if line.group().is_some() {
+                *header = Some(line);
+            }
+            Some((*header, line))
+        })
+    }
+
+    /// Return the index following the last non-blank line of a group, or `None` if the
+    /// group does not exist.
+    fn group_end(&self, group: Option<&str>) -> Option<usize> {
+        let start = match group {
+            Some(group) => {
+                1 + self.lines.iter().position(|line| line.group() == Some(group))?
+            },
+            None => 0,
+        };
+        let end = self.lines[start..]
+            .iter()
+            .position(|line| line.group().is_some())
+            .map_or(self.lines.len(), |i| start + i);
+        Some(
+            self.lines[start..end]
+                .iter()
+                .rposition(|line| !line.text().trim().is_empty())
+                .map_or(start, |i| start + i + 1),
+        )
+    }
+
+    fn set_groups_disabled(&self, groups: &[&str], disabled: bool) -> Result<()> {
+        let lines = self.lines.iter().map(|line| {
+            let mut line = line.clone();
+            if matches!(line.group(), Some(group) if groups.contains(&group)) {
+                line.set_disabled(disabled);
+            }
+            line
+        });
+        self.write_lines(lines)
+    }
+
+    fn set_disabled(&self, aliases: &[&str], disabled: bool) -> Result<()> {
+        alias::validate_aliases(aliases)?;
+        let lines = self.group_lines().map(|(header, line)| {
+            let applies = self.applies_to_host(header, line);
+            let mut line = line.clone();
+            if matches!(line.alias(), Some(Ok(alias)) if applies && aliases.contains(&alias)) {
+                line.set_disabled(disabled);
+            }
+            line
+        });
+        self.write_lines(lines)
+    }
+
+    /// Replace the content of the file with `lines` (see `with_preview`).
+    fn write_lines<L>(&self, lines: impl Iterator<Item = L>) -> Result<()>
+    where
+        L: std::borrow::Borrow<Line>, {
+        let lines: Vec<L> = lines.collect();
+        let old_text = fs::read_to_string(&self.file_name).unwrap_or_default();
+        let new_lines: Vec<&str> = lines.iter().map(|line| line.borrow().text()).collect();
+        if self.show_change(&old_text, &new_lines) {
+            return Ok(());
+        }
+        let new_text: String = new_lines.iter().map(|line| format!("{}\n", line)).collect();
+        self.record_history(&old_text, &new_text);
+        replace_file(&self.file_name, &new_text)
+    }
+
+    /// Show a change to the file as a unified diff (if `preview.dry_run` or `preview.diff`).
+    /// Returns `true` if the file must be left unchanged (a dry run).
+    fn show_change(&self, old_text: &str, new_lines: &[&str]) -> bool {
+        if self.preview.dry_run || self.preview.diff {
+            let old_lines: Vec<&str> = old_text.lines().collect();
+            print!("{}", diff::unified_diff(&self.file_name, &old_lines, new_lines));
+        }
+        self.preview.dry_run
+    }
+
+    /// Save the previous content of the file in the history (if `preview.history`), unless
+    /// the change leaves it unchanged. A failure is reported but does not stop the change.
+    fn record_history(&self, old_text: &str, new_text: &str) {
+        if let Some(history) = self.preview.history.as_ref().filter(|_| new_text != old_text) {
+            if let Err(error) = history.record(&self.file_name, old_text) {
+                log::warn!(
+                    r#"Could not save the history of "{}": {:#}"#,
+                    self.file_name,
+                    error
+                );
+            }
+        }
+    }
+
+    /// Replace the content of the file with `text` (e.g., a version from the history).
+    pub fn restore(&self, text: &str) -> Result<()> {
+        self.write_lines(text.lines().map(|text| Line::new(text.to_owned())))
+    }
+}
+
+/// Create a file containing `text` (see `AliasesFile::create`).
+fn create_file(filename: &str, text: &str, force: bool) -> Result<()> {
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create(force)
+        .create_new(!force)
+        .truncate(true)
+        .mode(FILE_MODE)
+        .open(filename)
+        .map_err(|error| match error.kind() {
+            io::ErrorKind::AlreadyExists => {
+                anyhow!(r#""{}" already exists (use --force to overwrite it)"#, filename)
+            },
+            _ => anyhow!(r#"could not create "{}": {}"#, filename, error),
+        })?;
+    file.write_all(text.as_bytes())
+        .with_context(|| format!(r#"could not write "{}""#, filename))?;
+    file.set_permissions(fs::Permissions::from_mode(FILE_MODE))
+        .with_context(|| format!(r#"could not set the mode of "{}""#, filename))?;
+    // SAFETY: geteuid has no preconditions and cannot fail.
+    if unsafe { libc::geteuid() } == 0 {
+        std::os::unix::fs::fchown(&file, Some(0), Some(0))
+            .with_context(|| format!(r#"could not change the owner of "{}""#, filename))?;
+    }
+    Ok(())
+}
+
+/// Replace the content of a file atomically (by renaming a new file over it), keeping its
+/// mode and owner. A symbolic link is followed (the file it points to is replaced). The
+/// replacement is atomic only if the directory is writable: otherwise, the file is rewritten
+/// in place (with a warning), and a reader may see a partial file.
+fn replace_file(file_name: &str, text: &str) -> Result<()> {
+    let path = fs::canonicalize(file_name)
+        .with_context(|| format!(r#"could not open "{}""#, file_name))?;
+    let metadata =
+        fs::metadata(&path).with_context(|| format!(r#"could not open "{}""#, file_name))?;
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let temp_path = path.with_file_name(format!(".{}.{}.tmp", name, std::process::id()));
+    let mut file = match fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(metadata.mode())
+        .open(&temp_path)
+    {
+        Ok(file) => file,
+        Err(error) if error.kind() == io::ErrorKind::PermissionDenied => {
+            log::warn!(
+                r#"Rewriting "{}" in place (not atomically; its directory is not writable: {})"#,
+                file_name,
+                error
+            );
+            return write_in_place(&path, text)
+                .with_context(|| format!(r#"could not write "{}""#, file_name));
+        },
+        Err(error) => {
+            return Err(error).with_context(|| format!(r#"could not write "{}""#, file_name))
+        },
+    };
+    let result = (|| -> io::Result<()> {
+        file.write_all(text.as_bytes())?;
+        file.set_permissions(metadata.permissions())?;
+        let file_metadata = file.metadata()?;
+        if (file_metadata.uid(), file_metadata.gid()) != (metadata.uid(), metadata.gid()) {
+            std::os::unix::fs::fchown(&file, Some(metadata.uid()), Some(metadata.gid()))?;
+        }
+        file.sync_all()?;
+        fs::rename(&temp_path, &path)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    result.with_context(|| format!(r#"could not write "{}""#, file_name))
+}
+
+/// Replace the content of a file by truncating and rewriting it.
+fn write_in_place(path: &Path, text: &str) -> io::Result<()> {
+    let mut file = fs::OpenOptions::new().write(true).truncate(true).open(path)?;
+    file.write_all(text.as_bytes())?;
+    file.sync_all()
 }
 
 //**********************************************************************************************
@@ -119,10 +667,14 @@ mod tests {
 
     use std::{self, fs, str};
     use std::io::{BufWriter, Error, Write};
+    use std::os::unix::fs::{MetadataExt, PermissionsExt};
+    use std::path::Path;
 
     use anyhow::Result;
 
-    use super::AliasesFile;
+    use super::{write_in_place, AliasesFile, Preview, FILE_TEMPLATE};
+    use crate::history::History;
+    use crate::{expiry, template};
 
     const FILE_HEADER: &str = "# This is a unit test temporary file";
     const VALID_ALIASES: [&str; 5] = ["a.local", "b.local", "c.local", "d.local", "e.local"];
@@ -264,6 +816,450 @@ mod tests {
         Ok(())
     }
 
+    /// Ensure disable keeps the line but stops treating the alias as published.
+    #[test]
+    fn disable_disables() {
+        let fn_name = stringify!(disable_disables);
+        for n in 1..5 {
+            let test_file = TestFile::new(fn_name, n, false);
+            let aliases_file = AliasesFile::from_file(&test_file.file_name, false).unwrap();
+            aliases_file
+                .disable(&[VALID_ALIASES[0]])
+                .unwrap_or_else(|error| panic!("Disable failed: {}", error));
+            let aliases_file = AliasesFile::from_file(&test_file.file_name, false).unwrap();
+            assert_eq!(aliases_file.lines().len(), n + 1);
+            assert_eq!(aliases_file.lines()[1].text(), format!("!{}", VALID_ALIASES[0]));
+            assert_eq!(aliases_file.alias_count(), n - 1);
+            assert!(!aliases_file.aliases().contains(&VALID_ALIASES[0]));
+            assert_eq!(aliases_file.disabled_aliases(), vec![VALID_ALIASES[0]]);
+        }
+    }
+
+    /// Ensure enable restores a disabled alias.
+    #[test]
+    fn enable_enables() {
+        let fn_name = stringify!(enable_enables);
+        for n in 1..5 {
+            let test_file = TestFile::new(fn_name, n, false);
+            let aliases_file = AliasesFile::from_file(&test_file.file_name, false).unwrap();
+            aliases_file.disable(&[VALID_ALIASES[0]]).unwrap();
+            let aliases_file = AliasesFile::from_file(&test_file.file_name, false).unwrap();
+            aliases_file
+                .enable(&[VALID_ALIASES[0]])
+                .unwrap_or_else(|error| panic!("Enable failed: {}", error));
+            let aliases_file = AliasesFile::from_file(&test_file.file_name, false).unwrap();
+            assert_eq!(aliases_file.lines()[1].text(), VALID_ALIASES[0]);
+            assert_eq!(aliases_file.alias_count(), n);
+            assert!(aliases_file.disabled_aliases().is_empty());
+        }
+    }
+
+    const GROUPED_CONTENT: &str = "# Header\n\
+                                   a.local\n\
+                                   \n\
+                                   [web]\n\
+                                   b.local\n\
+                                   !c.local\n\
+                                   \n\
+                                   ![db]\n\
+                                   d.local\n\
+                                   [web]\n\
+                                   e.local\n";
+
+    /// Ensure aliases are split into their groups.
+    #[test]
+    fn group_aliases_returns_enabled_aliases_by_group() {
+        let test_file =
+            TestFile::with_content(stringify!(group_aliases_returns), GROUPED_CONTENT);
+        let aliases_file = AliasesFile::from_file(&test_file.file_name, false).unwrap();
+        assert_eq!(aliases_file.group_aliases(), vec![
+            (None, vec!["a.local"]),
+            (Some("web"), vec!["b.local", "e.local"])
+        ]);
+        assert_eq!(aliases_file.aliases(), vec!["a.local", "b.local", "e.local"]);
+        assert_eq!(aliases_file.groups(), vec![("web", true), ("db", false)]);
+    }
+
+    /// Ensure enabling and disabling groups toggles the group headers.
+    #[test]
+    fn enable_and_disable_groups_toggle_headers() {
+        let test_file =
+            TestFile::with_content(stringify!(enable_and_disable_groups), GROUPED_CONTENT);
+        let aliases_file = AliasesFile::from_file(&test_file.file_name, false).unwrap();
+        aliases_file.enable_groups(&["db"]).unwrap();
+        let aliases_file = AliasesFile::from_file(&test_file.file_name, false).unwrap();
+        assert_eq!(aliases_file.groups(), vec![("web", true), ("db", true)]);
+        assert!(aliases_file.aliases().contains(&"d.local"));
+        aliases_file.disable_groups(&["web"]).unwrap();
+        let aliases_file = AliasesFile::from_file(&test_file.file_name, false).unwrap();
+        assert_eq!(aliases_file.aliases(), vec!["a.local", "d.local"]);
+        assert_eq!(aliases_file.lines()[9].text(), "![web]");
+    }
+
+    /// Ensure appending to a group inserts after the last line of the (first) section.
+    #[test]
+    fn append_to_group_appends_to_section() {
+        let test_file =
+            TestFile::with_content(stringify!(append_to_group_appends), GROUPED_CONTENT);
+        let aliases_file = AliasesFile::from_file(&test_file.file_name, false).unwrap();
+        aliases_file.append_to_group(&["x.local"], Some("web")).unwrap();
+        let aliases_file = AliasesFile::from_file(&test_file.file_name, false).unwrap();
+        assert_eq!(aliases_file.lines()[6].text(), "x.local");
+        aliases_file.append(&["y.local"]).unwrap();
+        let aliases_file = AliasesFile::from_file(&test_file.file_name, false).unwrap();
+        assert_eq!(aliases_file.lines()[2].text(), "y.local");
+        assert_eq!(aliases_file.group_aliases()[0], (None, vec!["a.local", "y.local"]));
+    }
+
+    /// Ensure appending to a missing group creates the group.
+    #[test]
+    fn append_to_group_creates_missing_group() {
+        let fn_name = stringify!(append_to_group_creates_missing_group);
+        let test_file = TestFile::new(fn_name, 2, false);
+        let aliases_file = AliasesFile::from_file(&test_file.file_name, false).unwrap();
+        aliases_file.append_to_group(&["x.local"], Some("new")).unwrap();
+        let aliases_file = AliasesFile::from_file(&test_file.file_name, false).unwrap();
+        assert_eq!(aliases_file.groups(), vec![("new", true)]);
+        assert_eq!(aliases_file.group_aliases()[1], (Some("new"), vec!["x.local"]));
+    }
+
+    const SELECTOR_CONTENT: &str = "a.local\n\
+                                    b.local @monitor-*\n\
+                                    c.local @web-01\n\
+                                    [db] @db-*\n\
+                                    d.local\n";
+
+    /// Ensure host selectors restrict the aliases to matching hosts.
+    #[test]
+    fn for_host_restricts_aliases_to_matching_hosts() {
+        let test_file =
+            TestFile::with_content(stringify!(for_host_restricts), SELECTOR_CONTENT);
+        let aliases_file = AliasesFile::from_file(&test_file.file_name, false).unwrap();
+        assert!(aliases_file.has_selectors());
+        assert_eq!(aliases_file.alias_count(), 4);
+        let aliases_file = aliases_file.for_host("monitor-01");
+        assert_eq!(aliases_file.aliases(), vec!["a.local", "b.local"]);
+        let aliases_file = aliases_file.for_host("db-01");
+        assert_eq!(aliases_file.aliases(), vec!["a.local", "d.local"]);
+        assert_eq!(aliases_file.group_aliases(), vec![
+            (None, vec!["a.local"]),
+            (Some("db"), vec!["d.local"])
+        ]);
+    }
+
+    /// Ensure lines for other hosts survive rewrites.
+    #[test]
+    fn lines_for_other_hosts_are_kept() {
+        let test_file =
+            TestFile::with_content(stringify!(lines_for_other_hosts), SELECTOR_CONTENT);
+        let aliases_file =
+            AliasesFile::from_file(&test_file.file_name, false).unwrap().for_host("web-01");
+        aliases_file.remove(&["b.local", "c.local"], false).unwrap();
+        let aliases_file =
+            AliasesFile::from_file(&test_file.file_name, false).unwrap().for_host("web-01");
+        aliases_file.disable(&["b.local", "d.local"]).unwrap();
+        let aliases_file = AliasesFile::from_file(&test_file.file_name, false).unwrap();
+        let lines: Vec<&str> = aliases_file.lines().iter().map(|line| line.text()).collect();
+        assert_eq!(lines, vec!["a.local", "b.local @monitor-*", "[db] @db-*", "d.local"]);
+    }
+
+    /// Ensure templates are expanded, but the file keeps the templates.
+    #[test]
+    fn expand_expands_templates() {
+        let content = "{hostname}-db.local\n{site}-web.local\na.local\n";
+        let test_file = TestFile::with_content(stringify!(expand_expands_templates), content);
+        let aliases_file = AliasesFile::from_file(&test_file.file_name, false).unwrap();
+        assert!(aliases_file.has_templates());
+        let mut variables = template::host_variables("db-01");
+        let aliases_file = aliases_file.expand(&variables);
+        assert_eq!(aliases_file.aliases(), vec!["db-01-db.local", "a.local"]);
+        assert_eq!(aliases_file.invalid_aliases(), vec!["{site}-web.local"]);
+        variables.insert(String::from("site"), String::from("east"));
+        let aliases_file = aliases_file.expand(&variables);
+        assert_eq!(aliases_file.aliases(), vec!["db-01-db.local", "east-web.local", "a.local"]);
+        variables.insert(String::from("site"), String::from("e_st"));
+        let aliases_file = aliases_file.expand(&variables);
+        assert_eq!(aliases_file.invalid_aliases(), vec!["e_st-web.local"]);
+        aliases_file.disable(&["a.local"]).unwrap();
+        let aliases_file = AliasesFile::from_file(&test_file.file_name, true).unwrap();
+        assert_eq!(aliases_file.lines()[0].text(), "{hostname}-db.local");
+    }
+
+    /// Ensure templates using undefined variables are left unexpanded by expand_defined.
+    #[test]
+    fn expand_defined_leaves_undefined_templates() {
+        let content = "{hostname}-db.local\n{site}-web.local\n{h-x.local\na.local\n";
+        let test_file = TestFile::with_content(
+            stringify!(expand_defined_leaves_undefined_templates),
+            content,
+        );
+        let aliases_file = AliasesFile::from_file(&test_file.file_name, true)
+            .unwrap()
+            .expand_defined(&template::host_variables("db-01"));
+        assert_eq!(aliases_file.aliases(), vec![
+            "db-01-db.local",
+            "{site}-web.local",
+            "a.local"
+        ]);
+        assert_eq!(aliases_file.unexpanded_templates(), vec!["{site}-web.local"]);
+        assert_eq!(aliases_file.invalid_aliases(), vec!["{h-x.local"]);
+    }
+
+    /// Ensure format rewrites the file canonically.
+    #[test]
+    fn format_formats() {
+        let content = "# Header\n\n\nB.local   \na.local # A\na.local  # A\n\n";
+        let test_file = TestFile::with_content(stringify!(format_formats), content);
+        let aliases_file = AliasesFile::from_file(&test_file.file_name, true).unwrap();
+        assert!(!aliases_file.is_formatted(false));
+        aliases_file.format(true).unwrap();
+        let aliases_file = AliasesFile::from_file(&test_file.file_name, false).unwrap();
+        assert!(aliases_file.is_formatted(true));
+        assert_eq!(
+            fs::read_to_string(&test_file.file_name).unwrap(),
+            "# Header\n\na.local # A\nb.local\n"
+        );
+    }
+
+    /// Ensure sync enables, removes, and appends aliases in one change.
+    #[test]
+    fn sync_syncs() {
+        let content =
+            "a.local # A\n!b.local # B\nc.local\nd.local @db-*\n{h}-x.local\n[web]\nw.local\n";
+        let test_file = TestFile::with_content(stringify!(sync_syncs), content);
+        let aliases_file =
+            AliasesFile::from_file(&test_file.file_name, false).unwrap().for_host("web-01");
+        assert!(aliases_file.sync(&["{h}-y.local"], false).is_err());
+        aliases_file.sync(&["a.local", "b.local", "e.local", "e.local"], false).unwrap();
+        assert_eq!(
+            fs::read_to_string(&test_file.file_name).unwrap(),
+            "a.local # A\nb.local # B\nd.local @db-*\n{h}-x.local\ne.local\n[web]\n"
+        );
+        let aliases_file = AliasesFile::from_file(&test_file.file_name, false).unwrap();
+        aliases_file.sync(&["f.local"], true).unwrap();
+        let aliases_file = AliasesFile::from_file(&test_file.file_name, false).unwrap();
+        assert_eq!(aliases_file.aliases(), vec![
+            "a.local",
+            "b.local",
+            "d.local",
+            "{h}-x.local",
+            "e.local",
+            "f.local"
+        ]);
+    }
+
+    /// Ensure sync publishes aliases in disabled groups or only in other hosts' lines.
+    #[test]
+    fn sync_publishes_inactive_aliases() {
+        let content = "a.local\nd.local @db-*\n![old]\nb.local # B\nc.local\n";
+        let test_file =
+            TestFile::with_content(stringify!(sync_publishes_inactive_aliases), content);
+        let aliases_file =
+            AliasesFile::from_file(&test_file.file_name, false).unwrap().for_host("web-01");
+        assert_eq!(aliases_file.present_aliases(), vec!["a.local"]);
+        aliases_file.sync(&["a.local", "b.local", "d.local"], true).unwrap();
+        assert_eq!(
+            fs::read_to_string(&test_file.file_name).unwrap(),
+            "a.local\nd.local @db-*\nb.local\nd.local @web-01\n![old]\nc.local\n"
+        );
+        let aliases_file =
+            AliasesFile::from_file(&test_file.file_name, false).unwrap().for_host("web-01");
+        assert_eq!(aliases_file.aliases(), vec!["a.local", "b.local", "d.local"]);
+        let aliases_file =
+            AliasesFile::from_file(&test_file.file_name, false).unwrap().for_host("db-01");
+        assert_eq!(aliases_file.aliases(), vec!["a.local", "d.local", "b.local"]);
+    }
+
+    /// Ensure an appended alias gets its description and attributes as a comment.
+    #[test]
+    fn append_with_comment_writes_the_comment() {
+        let test_file =
+            TestFile::with_content(stringify!(append_with_comment_writes_the_comment), "");
+        let aliases_file = AliasesFile::from_file(&test_file.file_name, false).unwrap();
+        let attributes = [("owner", "team-x")];
+        aliases_file
+            .append_with_comment(&["a.local"], None, Some("Grafana"), &attributes)
+            .unwrap();
+        assert_eq!(
+            fs::read_to_string(&test_file.file_name).unwrap(),
+            "a.local # Grafana owner=team-x\n"
+        );
+        assert!(aliases_file
+            .append_with_comment(&["b.local"], None, None, &[("owner", "a b")])
+            .is_err());
+        assert!(aliases_file
+            .append_with_comment(&["b.local"], None, Some("a\nb"), &[])
+            .is_err());
+    }
+
+    /// Ensure expired aliases are not published and prune removes them.
+    #[test]
+    fn expired_aliases_are_not_published_and_are_pruned() {
+        let content = concat!(
+            "a.local # expires=2026-10-18T14:00:00Z\n",
+            "b.local # Demo expires=2026-10-18T16:00:00Z\n",
+            "c.local\n"
+        );
+        let test_file =
+            TestFile::with_content(stringify!(expired_aliases_are_not_published), content);
+        let now = expiry::parse_timestamp("2026-10-18T15:00:00Z").unwrap();
+        let aliases_file =
+            AliasesFile::from_file(&test_file.file_name, false).unwrap().at_time(now);
+        assert_eq!(aliases_file.aliases(), vec!["b.local", "c.local"]);
+        assert_eq!(aliases_file.expired_aliases(), vec!["a.local"]);
+        assert_eq!(aliases_file.next_expiry(), expiry::parse_timestamp("2026-10-18T16:00:00Z"));
+        aliases_file.prune().unwrap();
+        assert_eq!(
+            fs::read_to_string(&test_file.file_name).unwrap(),
+            "b.local # Demo expires=2026-10-18T16:00:00Z\nc.local\n"
+        );
+    }
+
+    /// Ensure create writes the template, and overwrites an existing file only when forced.
+    #[test]
+    fn create_creates_from_the_template() {
+        let test_file = TestFile::with_content(stringify!(create_creates), "a.local\n");
+        assert!(AliasesFile::create(&test_file.file_name, false).is_err());
+        assert_eq!(fs::read_to_string(&test_file.file_name).unwrap(), "a.local\n");
+        AliasesFile::create(&test_file.file_name, true).unwrap();
+        assert_eq!(fs::read_to_string(&test_file.file_name).unwrap(), FILE_TEMPLATE);
+        fs::remove_file(&test_file.file_name).unwrap();
+        AliasesFile::create(&test_file.file_name, false).unwrap();
+        let metadata = fs::metadata(&test_file.file_name).unwrap();
+        assert_eq!(metadata.permissions().mode() & 0o777, 0o644);
+        let aliases_file = AliasesFile::from_file(&test_file.file_name, false).unwrap();
+        assert_eq!(aliases_file.alias_count(), 0);
+    }
+
+    /// Ensure write_new creates the file only when forced, saving the file it replaces.
+    #[test]
+    fn write_new_saves_the_replaced_file() {
+        let test_file =
+            TestFile::with_content(stringify!(write_new_saves_the_replaced_file), "a.local\n");
+        let dir = format!("{}.history", test_file.file_name);
+        let _ = fs::remove_dir_all(&dir);
+        let history = History::new(&dir, 5);
+        let preview = Preview { history: Some(history.clone()), ..Preview::default() };
+        let aliases_file =
+            AliasesFile::from_template(&test_file.file_name).with_preview(preview.clone());
+        assert!(aliases_file.write_new(false).is_err());
+        let dry_run = Preview { dry_run: true, ..preview.clone() };
+        aliases_file.with_preview(dry_run).write_new(true).unwrap();
+        assert_eq!(fs::read_to_string(&test_file.file_name).unwrap(), "a.local\n");
+        AliasesFile::from_template(&test_file.file_name)
+            .with_preview(preview)
+            .write_new(true)
+            .unwrap();
+        assert_eq!(fs::read_to_string(&test_file.file_name).unwrap(), FILE_TEMPLATE);
+        let versions = history.versions(&test_file.file_name).unwrap();
+        assert_eq!(versions.len(), 1);
+        assert_eq!(fs::read_to_string(&versions[0].path).unwrap(), "a.local\n");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// Ensure a dry run on a file from the template shows the new file without creating it.
+    #[test]
+    fn from_template_previews_a_new_file() {
+        let test_file = TestFile::with_content(stringify!(from_template_previews), "");
+        fs::remove_file(&test_file.file_name).unwrap();
+        let aliases_file = AliasesFile::from_template(&test_file.file_name)
+            .with_preview(Preview { dry_run: true, ..Preview::default() });
+        assert_eq!(aliases_file.lines().len(), FILE_TEMPLATE.lines().count());
+        assert_eq!(aliases_file.alias_count(), 0);
+        aliases_file.append(&["a.local"]).unwrap();
+        assert!(fs::metadata(&test_file.file_name).is_err());
+        fs::write(&test_file.file_name, "").unwrap();
+    }
+
+    /// Ensure a file rewritten in place (when its directory is not writable) keeps its inode.
+    #[test]
+    fn write_in_place_keeps_the_file() {
+        let test_file = TestFile::with_content(
+            stringify!(write_in_place_keeps_the_file),
+            "a.local\nb.local\n",
+        );
+        let inode = fs::metadata(&test_file.file_name).unwrap().ino();
+        write_in_place(Path::new(&test_file.file_name), "c.local\n").unwrap();
+        assert_eq!(fs::read_to_string(&test_file.file_name).unwrap(), "c.local\n");
+        assert_eq!(fs::metadata(&test_file.file_name).unwrap().ino(), inode);
+    }
+
+    /// Ensure rename keeps the line's position, marker, and comment, and rejects bad names.
+    #[test]
+    fn rename_renames_in_place() {
+        let test_file = TestFile::with_content(
+            stringify!(rename_renames_in_place),
+            "a.local # A\n!b.local # B\nc.local\n",
+        );
+        let aliases_file = AliasesFile::from_file(&test_file.file_name, false).unwrap();
+        aliases_file.rename("b.local", "x.local").unwrap();
+        assert_eq!(
+            fs::read_to_string(&test_file.file_name).unwrap(),
+            "a.local # A\n!x.local # B\nc.local\n"
+        );
+        assert!(aliases_file.rename("b.local", "a.local").is_err());
+        assert!(aliases_file.rename("z.local", "y.local").is_err());
+        assert!(aliases_file.rename("a.local", "Y.local").is_err());
+    }
+
+    /// Ensure a dry run leaves the file unchanged.
+    #[test]
+    fn dry_run_does_not_change_the_file() {
+        let content = "a.local # A\nb.local\n";
+        let test_file =
+            TestFile::with_content(stringify!(dry_run_does_not_change_the_file), content);
+        let preview = Preview { dry_run: true, ..Preview::default() };
+        let aliases_file =
+            AliasesFile::from_file(&test_file.file_name, false).unwrap().with_preview(preview);
+        aliases_file.append(&["c.local"]).unwrap();
+        aliases_file.remove(&["a.local"], false).unwrap();
+        assert_eq!(fs::read_to_string(&test_file.file_name).unwrap(), content);
+        let preview = Preview { diff: true, ..Preview::default() };
+        let aliases_file =
+            AliasesFile::from_file(&test_file.file_name, false).unwrap().with_preview(preview);
+        aliases_file.remove(&["a.local"], false).unwrap();
+        assert_eq!(fs::read_to_string(&test_file.file_name).unwrap(), "b.local\n");
+    }
+
+    /// Ensure changes save the previous content in the history, which can be restored.
+    #[test]
+    fn changes_are_saved_in_the_history_and_restored() {
+        let content = "a.local # A\nb.local\n";
+        let test_file = TestFile::with_content(
+            stringify!(changes_are_saved_in_the_history_and_restored),
+            content,
+        );
+        fs::set_permissions(&test_file.file_name, fs::Permissions::from_mode(0o640)).unwrap();
+        let dir = format!("{}.history", test_file.file_name);
+        let _ = fs::remove_dir_all(&dir);
+        let preview = Preview { history: Some(History::new(&dir, 5)), ..Preview::default() };
+        let history = preview.history.clone().unwrap();
+        let aliases_file = AliasesFile::from_file(&test_file.file_name, false)
+            .unwrap()
+            .with_preview(preview.clone());
+        aliases_file.remove(&["a.local"], false).unwrap();
+        // Writes that change nothing are not saved
+        let aliases_file = AliasesFile::from_file(&test_file.file_name, false)
+            .unwrap()
+            .with_preview(preview.clone());
+        aliases_file.remove(&["z.local"], false).unwrap();
+        assert_eq!(fs::read_to_string(&test_file.file_name).unwrap(), "b.local\n");
+        let metadata = fs::metadata(&test_file.file_name).unwrap();
+        assert_eq!(metadata.permissions().mode() & 0o777, 0o640);
+        let versions = history.versions(&test_file.file_name).unwrap();
+        assert_eq!(versions.len(), 1);
+        let saved = fs::read_to_string(&versions[0].path).unwrap();
+        assert_eq!(saved, content);
+        AliasesFile::from_file(&test_file.file_name, false)
+            .unwrap()
+            .with_preview(preview)
+            .restore(&saved)
+            .unwrap();
+        assert_eq!(fs::read_to_string(&test_file.file_name).unwrap(), content);
+        assert_eq!(history.versions(&test_file.file_name).unwrap().len(), 2);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
     /// Create and remove (using Drop trait) test files
     struct TestFile {
         file_name: String,
@@ -299,6 +1295,18 @@ mod tests {
                 });
             TestFile { file_name }
         }
+
+        fn with_content(file_name: &'static str, content: &str) -> TestFile {
+            let file_name = format!("data/{}.txt", file_name);
+            fs::write(&file_name, content).unwrap_or_else(|_| {
+                panic!(
+                    "Could not create test file: cwd={:?}, file={:?}",
+                    std::env::current_dir(),
+                    file_name
+                )
+            });
+            TestFile { file_name }
+        }
     }
 
     impl Drop for TestFile {
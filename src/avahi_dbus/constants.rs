@@ -13,6 +13,9 @@ pub const AVAHI_DBUS_PATH_SERVER: &str = "/";
 /// D- Bus name of the Avahi Entry Group service
 pub const AVAHI_DBUS_INTERFACE_ENTRY_GROUP: &str = "org.freedesktop.Avahi.EntryGroup";
 
+/// D-Bus name of the Avahi Record Browser service
+pub const AVAHI_DBUS_INTERFACE_RECORD_BROWSER: &str = "org.freedesktop.Avahi.RecordBrowser";
+
 //**********************************************************************************************
 // Client State
 //**********************************************************************************************
@@ -90,6 +93,35 @@ pub enum Protocol {
     INET6  = 1,
 }
 
+//**********************************************************************************************
+// Lookup Result Flags
+//**********************************************************************************************
+
+/// Lookup result flags (a bit mask)
+///
+/// Reference: Avahi source: `avahi-common/defs.h`
+#[allow(non_camel_case_types)]
+#[repr(u32)]
+pub enum LookupResultFlags {
+    /// The result came from the cache
+    CACHED    = 1,
+
+    /// The result came from wide-area DNS
+    WIDE_AREA = 2,
+
+    /// The result came from multicast DNS
+    MULTICAST = 4,
+
+    /// The result came from the local host
+    LOCAL     = 8,
+
+    /// The result was published by this Avahi daemon
+    OUR_OWN   = 16,
+
+    /// The result is static (e.g., from `/etc/avahi/hosts`)
+    STATIC    = 32,
+}
+
 //**********************************************************************************************
 // Record Class
 //**********************************************************************************************
@@ -200,6 +232,16 @@ mod tests {
         assert_eq!(Protocol::INET6 as i32, 1)
     }
 
+    #[test]
+    fn lookup_result_flags_constants_are_correct() {
+        assert_eq!(LookupResultFlags::CACHED as u32, 1);
+        assert_eq!(LookupResultFlags::WIDE_AREA as u32, 2);
+        assert_eq!(LookupResultFlags::MULTICAST as u32, 4);
+        assert_eq!(LookupResultFlags::LOCAL as u32, 8);
+        assert_eq!(LookupResultFlags::OUR_OWN as u32, 16);
+        assert_eq!(LookupResultFlags::STATIC as u32, 32);
+    }
+
     #[test]
     fn record_class_constants_are_correct() { assert_eq!(RecordClass::IN as u16, 1) }
 
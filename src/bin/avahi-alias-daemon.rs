@@ -6,10 +6,13 @@ use ::time::format_description::well_known::Rfc3339;
 use ::time::OffsetDateTime;
 use structopt::StructOpt;
 use anyhow::{Context, Result};
+use avahi_aliases::policy::Policy;
+use avahi_aliases::publish::{check_groups, publish_group, withdraw_groups, PublishedGroup};
+use avahi_aliases::template::{self, Variables};
 use avahi_aliases::{
     avahi_dbus, encoding, init_console_logging, init_syslog_logging, AliasesFile, DaemonOpts,
 };
-use avahi_dbus::{avahi, OrgFreedesktopAvahiEntryGroup, OrgFreedesktopAvahiServer};
+use avahi_dbus::{avahi, OrgFreedesktopAvahiServer};
 
 #[derive(PartialEq)]
 struct ModifiedSize {
@@ -32,6 +35,7 @@ fn inner_main(opts: DaemonOpts) -> Result<()> {
     init_logging(opts.common.verbose, opts.common.debug, opts.syslog)?;
     signon_app();
     let file_name = opts.common.file.as_str();
+    let policy_file = opts.common.policy.as_str();
     let dbus_connection = dbus::blocking::Connection::new_system()
         .with_context(|| "failed to open D-Bus connection to Avahi service")?;
     let avahi_server_proxy = dbus_connection.with_proxy(
@@ -43,7 +47,9 @@ fn inner_main(opts: DaemonOpts) -> Result<()> {
     load_publish_loop(
         &avahi_server_proxy,
         file_name,
+        policy_file,
         time::Duration::new(opts.polling_interval, 0),
+        &opts.definitions.into_iter().collect(),
     )?;
     Ok(())
 }
@@ -67,89 +73,191 @@ fn get_metadata(file_name: &str) -> Result<ModifiedSize> {
         .with_context(|| format!(r#"could not get last modified for "{}""#, file_name))
 }
 
-fn load_aliases(file_name: &str, modified_size: &ModifiedSize) -> Result<AliasesFile> {
+fn load_aliases(
+    file_name: &str, modified_size: &ModifiedSize, host_name: &str, definitions: &Variables,
+) -> Result<AliasesFile> {
     let last_modified: OffsetDateTime = modified_size.last_modified.into();
     log::debug!(
         "Loading aliases from {:?} (modified {})",
         file_name,
         last_modified.format(&Rfc3339).unwrap()
     );
-    AliasesFile::from_file(file_name, true)
+    // Host selectors and templates use the Avahi host name
+    let mut variables = template::host_variables(host_name);
+    variables.extend(definitions.clone());
+    Ok(AliasesFile::from_file(file_name, true)?.for_host(host_name).expand(&variables))
+}
+
+/// Load the policy file (an empty policy if it does not exist).
+fn load_policy(policy_file: &str) -> Result<Policy> {
+    let policy = Policy::from_file(policy_file)?;
+    if !policy.is_empty() {
+        log::info!(r#"Loaded the policy from "{}""#, policy_file);
+    }
+    Ok(policy)
 }
 
 fn load_publish_loop(
-    avahi_server_proxy: &avahi_dbus::DBusProxy, file_name: &str,
-    polling_interval: time::Duration,
+    avahi_server_proxy: &avahi_dbus::DBusProxy, file_name: &str, policy_file: &str,
+    polling_interval: time::Duration, definitions: &Variables,
 ) -> Result<()> {
     let mut modified_size = ModifiedSize { last_modified: time::UNIX_EPOCH, len: 0 };
+    let mut policy_modified_size = get_metadata(policy_file).ok();
+    let mut policy = load_policy(policy_file)?;
+    let mut host_name = String::new();
+    let mut published_groups = Vec::<PublishedGroup<'_>>::new();
+    let mut next_expiry: Option<OffsetDateTime> = None;
 
     loop {
         log::debug!(r#"Retrieving metadata for "{}""#, file_name);
         let new_modified_size = get_metadata(file_name)?;
-        if new_modified_size != modified_size {
-            let aliases_file = load_aliases(file_name, &new_modified_size)?;
+        // Avahi may be briefly unavailable (e.g., a D-Bus timeout); retry on the next poll
+        let new_host_name = match avahi_server_proxy.get_host_name() {
+            Ok(new_host_name) => new_host_name,
+            Err(error) => {
+                log::error!("Could not get the host name from Avahi: {}", error);
+                thread::sleep(polling_interval);
+                continue;
+            },
+        };
+        let new_policy_modified_size = get_metadata(policy_file).ok();
+        let mut policy_changed = false;
+        if new_policy_modified_size != policy_modified_size {
+            log::info!(r#"Policy file "{}" changed"#, policy_file);
+            policy_modified_size = new_policy_modified_size;
+            // A policy that cannot be loaded (e.g., while it is being edited) is reported,
+            // and the last good policy is kept.
+            match load_policy(policy_file) {
+                Ok(new_policy) => {
+                    policy = new_policy;
+                    policy_changed = true;
+                },
+                Err(error) => log::error!("Could not reload the policy: {:#}", error),
+            }
+        }
+        if !host_name.is_empty() && new_host_name != host_name {
+            log::info!("Host name changed from {:?} to {:?}", host_name, new_host_name);
+        }
+        let expired = next_expiry.filter(|expiry| *expiry <= OffsetDateTime::now_utc());
+        if let Some(expiry) = expired {
+            log::info!("Aliases expired at {}", expiry.format(&Rfc3339).unwrap());
+        }
+        if new_modified_size != modified_size
+            || new_host_name != host_name
+            || expired.is_some()
+            || policy_changed
+        {
+            let aliases_file =
+                load_aliases(file_name, &new_modified_size, &new_host_name, definitions)?;
             log::info!(r#"Loaded {} aliases from "{}""#, aliases_file.alias_count(), file_name);
-            publish_aliases(avahi_server_proxy, &aliases_file, file_name, &new_modified_size)?;
+            withdraw_groups(&published_groups);
+            published_groups = publish_aliases(
+                avahi_server_proxy,
+                &aliases_file,
+                &policy,
+                file_name,
+                &new_modified_size,
+            )?;
             modified_size = new_modified_size;
+            host_name = new_host_name;
+            next_expiry = aliases_file.next_expiry();
+            if let Some(expiry) = next_expiry {
+                log::debug!("Next alias expires at {}", expiry.format(&Rfc3339).unwrap());
+            }
         } else {
             log::debug!(r#"Alias file "{}" has not changed"#, file_name);
         }
-        thread::sleep(polling_interval);
+        thread::sleep(sleep_interval(polling_interval, next_expiry));
+        check_groups(&mut published_groups);
     }
 }
 
-fn publish_aliases<'a>(
-    avahi_server_proxy: &avahi_dbus::DBusProxy, aliases_file: &AliasesFile, file_name: &'a str,
-    modified_size: &ModifiedSize,
-) -> Result<()> {
+/// The time to sleep: the polling interval, or less if an alias expires sooner.
+fn sleep_interval(
+    polling_interval: time::Duration, next_expiry: Option<OffsetDateTime>,
+) -> time::Duration {
+    match next_expiry {
+        Some(expiry) => time::Duration::try_from(expiry - OffsetDateTime::now_utc())
+            .unwrap_or(time::Duration::ZERO)
+            .min(polling_interval),
+        None => polling_interval,
+    }
+}
+
+fn publish_aliases<'c>(
+    avahi_server_proxy: &avahi_dbus::DBusProxy<'_, 'c>, aliases_file: &AliasesFile,
+    policy: &Policy, file_name: &str, modified_size: &ModifiedSize,
+) -> Result<Vec<PublishedGroup<'c>>> {
     let last_modified: OffsetDateTime = modified_size.last_modified.into();
+    for alias in aliases_file.invalid_aliases() {
+        log::info!(r#"WARNING: invalid alias "{}" ignored"#, alias);
+    }
+    for alias in aliases_file.expired_aliases() {
+        log::info!(r#"Alias "{}" has expired; not publishing it"#, alias);
+    }
     if aliases_file.alias_count() == 0 {
         log::warn!(
             r#"No aliases in "{}" (modified {})"#,
             file_name,
             last_modified.format(&Rfc3339).unwrap()
         );
-        return Ok(());
+        return Ok(vec![]);
     }
 
     let fqdn = avahi_server_proxy.get_host_name_fqdn()?;
     log::debug!(r#"Publishing aliases from "{}" for "{}""#, file_name, fqdn,);
 
+    // Each group is published in its own entry group so a collision only withdraws the
+    // aliases in the group with the collision.
     let rdata = encoding::encode_rdata(&fqdn);
-    let entry_group_path = avahi_server_proxy.entry_group_new()?;
-    let entry_group_proxy = avahi_server_proxy.connection.with_proxy(
-        avahi::AVAHI_DBUS_NAME,
-        entry_group_path,
-        avahi_aliases::DEFAULT_TIMEOUT,
-    );
-    for alias in aliases_file.all_aliases() {
-        match alias {
-            Ok(alias) => {
-                log::info!("Publishing alias {}", alias);
-                entry_group_proxy.add_record(
-                    avahi::Interface::UNSPECIFIED as i32,
-                    avahi::Protocol::UNSPEC as i32,
-                    0,
-                    alias,
-                    avahi::RecordClass::IN as u16,
-                    avahi::RecordType::CNAME as u16,
-                    60,
-                    rdata.clone(),
-                )?;
-                // let cname_record =
-                //     AvahiRecord::new_cname(alias, time::Duration::from_secs(60), &rdata);
-            },
-            Err(a) => log::info!(r#"WARNING: invalid alias "{}" ignored"#, a),
+    let mut published_groups = Vec::<PublishedGroup<'c>>::new();
+    let mut alias_count = 0;
+    for (group, aliases) in aliases_file.group_aliases() {
+        let aliases = allowed_aliases(policy, aliases, &mut alias_count);
+        if aliases.is_empty() {
+            continue;
+        }
+        let name = group_name(group);
+        match publish_group(avahi_server_proxy, &name, &aliases, &rdata) {
+            Ok(published_group) => published_groups.push(published_group),
+            Err(error) => log::error!("Could not publish {}: {}", name, error),
         }
     }
-    entry_group_proxy.commit()?;
     log::info!(
-        "Published {} aliases from {:?} (modified {})",
-        aliases_file.alias_count(),
+        "Published {} aliases in {} groups from {:?} (modified {})",
+        alias_count,
+        published_groups.len(),
         file_name,
         last_modified.format(&Rfc3339).unwrap()
     );
-    Ok(())
+    Ok(published_groups)
+}
+
+/// Return the aliases the policy allows, counting them in `alias_count` (the aliases allowed
+/// so far). The others are logged.
+fn allowed_aliases<'a>(
+    policy: &Policy, aliases: Vec<&'a str>, alias_count: &mut usize,
+) -> Vec<&'a str> {
+    let mut allowed = Vec::<&str>::with_capacity(aliases.len());
+    for alias in aliases {
+        if let Some(violation) = policy.violation(alias) {
+            log::warn!(r#"Policy: not publishing "{}" ({})"#, alias, violation);
+        } else if policy.max_aliases().is_some_and(|max| *alias_count >= max) {
+            let max = policy.max_aliases().unwrap_or_default();
+            log::warn!(r#"Policy: not publishing "{}" (more than {} aliases)"#, alias, max);
+        } else {
+            allowed.push(alias);
+            *alias_count += 1;
+        }
+    }
+    allowed
+}
+
+fn group_name(group: Option<&str>) -> String {
+    match group {
+        Some(group) => format!(r#"group "{}""#, group),
+        None => String::from("default group"),
+    }
 }
 
 fn signon_app() {
@@ -1,83 +1,1306 @@
 #![warn(clippy::all)]
 
 use std::collections::HashSet;
+use std::io::{self, Write};
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::ffi::{CString, OsString};
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::process::ExitStatusExt;
+use std::{env, fs, panic, process, slice, thread};
+use std::time::{Duration, Instant};
 
-use anyhow::{Result};
+use anyhow::{anyhow, Context, Result};
+use regex::Regex;
+use avahi_aliases::avahi_dbus::{self, avahi, OrgFreedesktopAvahiServer};
+use avahi_aliases::check::{self, Finding, Severity};
 use avahi_aliases::{
-    init_console_logging, validate_aliases, AliasesFile, Command, CommandOpts,
+    completions, diff, encoding, glob, init_console_logging, is_valid_alias, new_alias,
+    new_alias_or_template, template, validate_aliases, validate_aliases_or_templates,
+    AliasesFile, Command, CommandOpts, GroupCommand, Line, Preview, OWNER_ATTRIBUTE,
 };
+use avahi_aliases::doctor::{self, Diagnosis, Health};
+use avahi_aliases::expiry::{self, EXPIRES_ATTRIBUTE};
+use avahi_aliases::history::{History, HISTORY_DIR};
+use avahi_aliases::interchange::{self, ExchangeFormat};
+use avahi_aliases::lookup::{
+    self, alias_status, resolve_host_name, AliasStatus, Answer, LocalHost,
+};
+use avahi_aliases::output::{self, OutputFormat, Value};
+use avahi_aliases::policy::{self, Policy};
+use avahi_aliases::publish::{check_groups, publish_group, withdraw_groups, PublishedGroup};
+
+/// Exit status: success
+const EXIT_SUCCESS: i32 = 0;
+/// Exit status: the command failed
+const EXIT_FAILURE: i32 = 1;
+/// Exit status: `sync` changed the aliases file
+const EXIT_SYNC_CHANGED: i32 = 2;
+/// Exit status: `check` found errors
+const EXIT_CHECK_ERRORS: i32 = 2;
+/// Exit status: `check` found warnings (but no errors)
+const EXIT_CHECK_WARNINGS: i32 = 3;
+/// Exit status: `doctor` found failures
+const EXIT_DOCTOR_FAILURES: i32 = 2;
+/// Exit status: `doctor` found warnings (but no failures)
+const EXIT_DOCTOR_WARNINGS: i32 = 3;
 
 #[paw::main]
 fn main(opts: CommandOpts) {
-    if let Err(error) = inner_main(opts) {
-        log::error!("Error: {}", error);
-        std::process::exit(1);
+    match inner_main(opts) {
+        Ok(status) => std::process::exit(status),
+        Err(error) => {
+            log::error!("Error: {}", error);
+            std::process::exit(EXIT_FAILURE)
+        },
     }
-    std::process::exit(0);
 }
 
-fn inner_main(opts: CommandOpts) -> Result<()> {
+fn inner_main(opts: CommandOpts) -> Result<i32> {
     init_console_logging(opts.common.verbose, opts.common.debug)?;
+    let filename = opts.common.file.as_str();
+    let preview = &Preview {
+        dry_run: opts.dry_run,
+        diff: opts.diff,
+        history: file_history(opts.history_dir.as_deref(), opts.history_limit),
+    };
+    let policy = || Policy::from_file(&opts.common.policy);
     match opts.cmd {
-        Command::Add { aliases } => add(&opts.common.file, &aliases),
-        Command::List {} => list(&opts.common.file),
-        Command::Remove { aliases, force } => remove(&opts.common.file, &aliases, force),
+        Command::Edit {} => edit(filename, preview),
+        Command::Status { aliases, format } => status(filename, &aliases, format),
+        Command::Resolve { name, format } => resolve(&name, format),
+        Command::Publish { aliases } => publish(&aliases),
+        Command::Run { aliases, command } => return run(&aliases, &command),
+        Command::Sync { aliases, from, keep_extra } => {
+            return sync(filename, &aliases, from.as_deref(), keep_extra, &policy()?, preview)
+        },
+        Command::Check { files, format } => return check(filename, &files, format),
+        Command::Doctor { format } => return doctor(filename, format),
+        Command::Init { force } => init(filename, force, preview),
+        Command::Add {
+            aliases,
+            from,
+            group,
+            allow_conflict,
+            no_conflict_check,
+            comment,
+            owner,
+            expires,
+            create,
+        } => {
+            let aliases = collect_aliases(&aliases, from.as_deref(), true)?;
+            let aliases_file = match (create, Path::new(filename).exists()) {
+                // A dry run shows the new file (the template and the added aliases)
+                (true, false) if preview.dry_run => AliasesFile::from_template(filename),
+                (true, false) => {
+                    init(filename, false, preview)?;
+                    load_aliases_file(filename, false)?
+                },
+                (false, false) => {
+                    return Err(anyhow!(
+                        r#""{}" does not exist (use add --create or init to create it)"#,
+                        filename
+                    ))
+                },
+                _ => load_aliases_file(filename, false)?,
+            };
+            let expires = expires.map(expiry::format_expiry);
+            let attributes: Vec<(&str, &str)> = owner
+                .iter()
+                .map(|owner| (OWNER_ATTRIBUTE, owner.as_str()))
+                .chain(expires.iter().map(|expires| (EXPIRES_ATTRIBUTE, expires.as_str())))
+                .collect();
+            check_added_aliases(&aliases_file, &policy()?, &aliases)?;
+            let comment = comment.as_deref().filter(|comment| !comment.is_empty());
+            let conflict_check = match (no_conflict_check, allow_conflict) {
+                (true, _) => ConflictCheck::Skip,
+                (_, true) => ConflictCheck::Warn,
+                _ => ConflictCheck::Fail,
+            };
+            add(
+                aliases_file,
+                &aliases,
+                group.as_deref(),
+                conflict_check,
+                comment,
+                &attributes,
+                preview,
+            )
+        },
+        Command::List { all_hosts, format, valid_only, invalid_only, owner } => {
+            let validity = match (valid_only, invalid_only) {
+                (true, _) => Some(true),
+                (_, true) => Some(false),
+                _ => None,
+            };
+            list(filename, all_hosts, format, validity, owner.as_deref())
+        },
+        Command::Remove { aliases, from, glob, regex, force, yes } => {
+            match pattern(glob.as_deref(), regex.as_deref())? {
+                Some(pattern) => remove_matching(filename, pattern, force, yes, preview),
+                None => remove(filename, &aliases, from.as_deref(), force, preview),
+            }
+        },
+        Command::Disable { aliases } => disable(filename, &aliases, preview),
+        Command::Enable { aliases } => enable(filename, &aliases, preview),
+        Command::Rename { old, new } => rename(filename, &old, &new, &policy()?, preview),
+        Command::Prune {} => prune(filename, preview),
+        Command::Completions { shell } => {
+            print!("{}", completions::completions(shell)?);
+            Ok(())
+        },
+        Command::Complete { command } => complete(filename, &command),
+        Command::History { format } => history(filename, format, preview),
+        Command::Undo { version } => undo(filename, version, preview),
+        Command::Export { format, target, addresses } => {
+            export(filename, format, target.as_deref(), &addresses)
+        },
+        Command::Import { files, format, group } => {
+            import(filename, &files, format, group.as_deref(), &policy()?, preview)
+        },
+        Command::Fmt { check, sort } => fmt(filename, check, sort, preview),
+        Command::Group { cmd } => match cmd {
+            GroupCommand::List {} => list_groups(filename),
+            GroupCommand::Enable { groups } => enable_groups(filename, &groups, preview),
+            GroupCommand::Disable { groups } => disable_groups(filename, &groups, preview),
+        },
     }
+    .map(|_| EXIT_SUCCESS)
 }
 
-fn add(filename: &str, arg_aliases: &[String]) -> Result<()> {
-    // Validate command line aliases
-    validate_aliases(arg_aliases)?;
-    // Load the avahi-aliases file. (fails if there are invalid aliases.)
-    let aliases_file = AliasesFile::from_file(filename, false)?;
+/// Create an aliases file (see `AliasesFile::create`).
+fn init(filename: &str, force: bool, preview: &Preview) -> Result<()> {
+    if !preview.dry_run {
+        log::info!("Creating {}", filename);
+    }
+    AliasesFile::from_template(filename).with_preview(preview.clone()).write_new(force)
+}
+
+fn add(
+    aliases_file: AliasesFile, arg_aliases: &[String], group: Option<&str>,
+    conflict_check: ConflictCheck, comment: Option<&str>, attributes: &[(&str, &str)],
+    preview: &Preview,
+) -> Result<()> {
+    // Validate the group (the aliases were validated by collect_aliases)
+    validate_group(group)?;
+    let aliases_file = aliases_file.with_preview(preview.clone());
     // new_aliases are commane line aliases not already in the file (don't add dups!).
-    let (_, new_aliases) =
-        split_aliases(&aliases_file.aliases().into_iter().collect(), arg_aliases);
+    let (_, new_aliases) = split_aliases(&present_aliases(&aliases_file), arg_aliases);
+    let disabled_aliases = aliases_file.disabled_aliases();
+    for alias in arg_aliases.iter().filter(|a| disabled_aliases.contains(&a.as_str())) {
+        log::warn!("Alias {:?} is disabled; use enable to publish it", alias);
+    }
+    check_conflicts(&new_aliases, conflict_check)?;
     for alias in new_aliases.iter() {
-        log::info!("Adding {:?} to {}", alias, filename);
+        log::info!("Adding {:?} to {}", alias, aliases_file.file_name());
     }
-    aliases_file.append(&new_aliases)
+    aliases_file.append_with_comment(&new_aliases, group, comment, attributes)
 }
 
-fn list(filename: &str) -> Result<()> {
-    let aliases_file = AliasesFile::from_file(filename, true)?;
-    if aliases_file.alias_count() == 0 {
+fn validate_group(group: Option<&str>) -> Result<()> {
+    match group {
+        Some(group) if Line::new(format!("[{}]", group)).group() != Some(group) => {
+            Err(anyhow!(r#"invalid group: "{}""#, group))
+        },
+        _ => Ok(()),
+    }
+}
+
+/// Check aliases to be added against the policy (for the user making the change).
+fn check_added_aliases(
+    aliases_file: &AliasesFile, policy: &Policy, arg_aliases: &[String],
+) -> Result<()> {
+    if policy.is_empty() {
+        return Ok(());
+    }
+    let (_, new_aliases) = split_aliases(&present_aliases(aliases_file), arg_aliases);
+    let alias_count = aliases_file.aliases().len() + new_aliases.len();
+    policy.validate_aliases(&new_aliases, &policy::user_groups(), alias_count)
+}
+
+/// How `add` treats aliases already claimed by another host on the network
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ConflictCheck {
+    Fail,
+    Warn,
+    Skip,
+}
+
+/// The most aliases looked up on the network at once (each unclaimed alias waits for Avahi's
+/// resolver to time out)
+const CONFLICT_CHECK_THREADS: usize = 16;
+
+/// Fail (or warn) if any alias already resolves to another host on the network. Templates are
+/// not checked. The aliases are looked up concurrently, each thread with its own connection.
+fn check_conflicts(aliases: &[&str], conflict_check: ConflictCheck) -> Result<()> {
+    let aliases: Vec<&str> =
+        aliases.iter().copied().filter(|a| !template::is_template(a)).collect();
+    if aliases.is_empty() || conflict_check == ConflictCheck::Skip {
+        return Ok(());
+    }
+    // Avahi may not be running (e.g., when preparing a file for another host)
+    let local_host = match dbus_connection()
+        .and_then(|dbus_connection| LocalHost::new(&avahi_server_proxy(&dbus_connection)))
+    {
+        Ok(local_host) => local_host,
+        Err(error) => {
+            log::warn!("Could not check the network for conflicts: {}", error);
+            return Ok(());
+        },
+    };
+    let chunk_size = aliases.len().div_ceil(CONFLICT_CHECK_THREADS);
+    let claimants: Vec<(&str, Result<Option<String>>)> = thread::scope(|scope| {
+        let local_host = &local_host;
+        let threads: Vec<_> = aliases
+            .chunks(chunk_size)
+            .map(|chunk| {
+                scope.spawn(move || {
+                    let dbus_connection = dbus_connection();
+                    let claimant = |alias: &str| {
+                        log::debug!("Checking the network for {:?}", alias);
+                        match &dbus_connection {
+                            Ok(dbus_connection) => lookup::claimant(
+                                &avahi_server_proxy(dbus_connection),
+                                local_host,
+                                alias,
+                            ),
+                            Err(error) => Err(anyhow!("{:#}", error)),
+                        }
+                    };
+                    chunk.iter().map(|alias| (*alias, claimant(alias))).collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        threads
+            .into_iter()
+            .flat_map(|thread| {
+                thread.join().unwrap_or_else(|panic| panic::resume_unwind(panic))
+            })
+            .collect()
+    });
+    let mut conflicts = Vec::<String>::new();
+    for (alias, claimant) in claimants {
+        match (claimant, conflict_check) {
+            (Ok(None), _) => (),
+            (Ok(Some(owner)), ConflictCheck::Fail) => {
+                conflicts.push(format!("{:?} is already claimed by {}", alias, owner))
+            },
+            (Ok(Some(owner)), _) => {
+                log::warn!("Alias {:?} is already claimed by {}", alias, owner)
+            },
+            (Err(error), _) => {
+                log::warn!("Could not check the network for {:?}: {:#}", alias, error)
+            },
+        }
+    }
+    match conflicts.is_empty() {
+        true => Ok(()),
+        false => Err(anyhow!("{} (use --allow-conflict to add anyway)", conflicts.join("; "))),
+    }
+}
+
+fn list(
+    filename: &str, all_hosts: bool, format: OutputFormat, validity: Option<bool>,
+    owner: Option<&str>,
+) -> Result<()> {
+    let aliases_file = match all_hosts {
+        true => AliasesFile::from_file(filename, true)?,
+        false => load_aliases_file(filename, true)?,
+    };
+    // Templates are expanded (and their expansions validated) for the local host; without a
+    // host (--all-hosts, or Avahi not running), or with variables defined only on the daemon
+    // command line, they are listed as templates.
+    let host_name = match (all_hosts, aliases_file.host_name()) {
+        (true, _) => None,
+        (false, Some(host_name)) => Some(host_name.to_owned()),
+        (false, None) if aliases_file.has_templates() => avahi_host_name().ok(),
+        (false, None) => None,
+    };
+    let aliases_file = match &host_name {
+        Some(host_name) => aliases_file.expand_defined(&template::host_variables(host_name)),
+        None => aliases_file,
+    };
+    if aliases_file.all_aliases().is_empty() && format == OutputFormat::Text {
         log::warn!(r#"No aliases in "{}""#, filename);
         return Ok(());
     }
-    for alias in aliases_file.all_aliases() {
+    let enabled_aliases = aliases_file.aliases();
+    let expired_aliases = aliases_file.expired_aliases();
+    let lines = aliases_file
+        .group_lines()
+        .zip(1..)
+        .filter(|((h, l), _)| aliases_file.applies_to_host(*h, l))
+        .filter_map(|((header, line), number)| Some((header, line, line.alias()?, number)))
+        .filter(|(_, _, alias, _)| validity.is_none_or(|valid| alias.is_ok() == valid))
+        .filter(|(_, line, ..)| {
+            owner.is_none_or(|o| line.attribute(OWNER_ATTRIBUTE) == Some(o))
+        });
+    if format != OutputFormat::Text {
+        // Every alias targets the local host (when Avahi is available)
+        let target = avahi_host_name_fqdn().ok();
+        let records: Vec<Vec<Value>> = lines
+            .map(|(header, line, alias, number)| {
+                let valid = alias.is_ok();
+                let alias = alias.unwrap_or_else(|invalid_alias| invalid_alias);
+                vec![
+                    Value::Text(alias.to_owned()),
+                    Value::Number(number),
+                    Value::Bool(valid),
+                    Value::Bool(enabled_aliases.contains(&alias)),
+                    Value::optional_text(header.and_then(Line::group)),
+                    Value::Text(line_selectors(header, line).join(" ")),
+                    Value::optional_text(line.description()),
+                    Value::optional_text(line.attribute(OWNER_ATTRIBUTE)),
+                    Value::optional_text(line.attribute(EXPIRES_ATTRIBUTE)),
+                    Value::optional_text(target.as_deref()),
+                    Value::optional_text(line.template()),
+                ]
+            })
+            .collect();
+        let columns = [
+            "alias", "line", "valid", "enabled", "group", "hosts", "comment", "owner",
+            "expires", "target", "template",
+        ];
+        print!("{}", output::encode_records(format, &columns, &records));
+        return Ok(());
+    }
+    for (header, line, alias, _) in lines {
+        let selectors: String =
+            line_selectors(header, line).iter().map(|s| format!(" @{}", s)).collect();
+        let template = match line.template() {
+            Some(template) if alias == Ok(template) => String::from(" (template)"),
+            Some(template) => format!(" (template: {})", template),
+            None => String::new(),
+        };
+        let attributes: String = [("owner", OWNER_ATTRIBUTE), ("expires", EXPIRES_ATTRIBUTE)]
+            .iter()
+            .filter_map(|(label, key)| Some(format!(" ({}: {})", label, line.attribute(key)?)))
+            .collect();
+        let description = line.description().map_or(String::new(), |d| format!(" # {}", d));
+        let attributes = template + &attributes + &description;
         match alias {
-            Ok(alias) => println!("{}", alias),
-            Err(invalid_alias) => {
-                println!(r#"ERROR: invalid alias "{}""#, invalid_alias)
+            Ok(alias) if expired_aliases.contains(&alias) => {
+                println!("{}{} (expired){}", alias, selectors, attributes)
+            },
+            Ok(alias) if !enabled_aliases.contains(&alias) => {
+                println!("{}{} (disabled){}", alias, selectors, attributes)
+            },
+            Ok(alias) => println!("{}{}{}", alias, selectors, attributes),
+            Err(invalid_alias) => match line.template() {
+                Some(template) if template != invalid_alias => println!(
+                    r#"ERROR: invalid alias "{}" (template: {})"#,
+                    invalid_alias, template
+                ),
+                _ => println!(r#"ERROR: invalid alias "{}""#, invalid_alias),
             },
         }
     }
     Ok(())
 }
 
-fn remove(filename: &str, arg_aliases: &[String], force: bool) -> Result<()> {
-    // Validate command line aliases
-    validate_aliases(arg_aliases)?;
+/// The host selectors of a line and its group header.
+fn line_selectors<'a>(header: Option<&'a Line>, line: &'a Line) -> Vec<&'a str> {
+    header.map_or(vec![], Line::selectors).into_iter().chain(line.selectors()).collect()
+}
+
+fn remove(
+    filename: &str, arg_aliases: &[String], from: Option<&str>, force: bool, preview: &Preview,
+) -> Result<()> {
+    // Collect and validate command line aliases
+    let arg_aliases = &collect_aliases(arg_aliases, from, false)?;
     // Load the avahi-aliases file. (Fails if there are invalid aliases
     // unless --force is in play.)
-    let aliases_file = AliasesFile::from_file(filename, force)?;
-    // If --force and there are invalid aliases, delete them
-    if !aliases_file.invalid_aliases().is_empty() {
-        for alias in aliases_file.invalid_aliases().iter() {
-            log::info!("Removing invalid alias {:?} from {}", alias, filename);
-        }
-        aliases_file.remove(&aliases_file.invalid_aliases(), true)?;
+    let aliases_file = load_aliases_file(filename, force)?.with_preview(preview.clone());
+    // If --force and there are invalid aliases, they are removed too
+    for alias in aliases_file.invalid_aliases().iter() {
+        log::info!("Removing invalid alias {:?} from {}", alias, filename);
     }
     // extant_aliases is the list of aliases to be removed
-    let (extant_aliases, _) =
-        split_aliases(&aliases_file.aliases().into_iter().collect(), arg_aliases);
+    let (extant_aliases, _) = split_aliases(&present_aliases(&aliases_file), arg_aliases);
     for alias in extant_aliases.iter() {
         log::info!("Removing alias {:?} from {}", alias, filename);
     }
     aliases_file.remove(&extant_aliases, false)
 }
 
+/// Build the pattern for `remove --glob` or `remove --regex`, returned with its text.
+/// (Regular expressions are not anchored; globs match the whole alias.)
+fn pattern<'a>(
+    glob: Option<&'a str>, regex: Option<&'a str>,
+) -> Result<Option<(&'a str, Regex)>> {
+    match (glob, regex) {
+        (Some(glob), _) => Ok(Some((glob, glob::glob_to_regex(glob)))),
+        (None, Some(regex)) => match Regex::new(regex) {
+            Ok(re) => Ok(Some((regex, re))),
+            Err(error) => Err(anyhow!("invalid regular expression {:?}: {}", regex, error)),
+        },
+        (None, None) => Ok(None),
+    }
+}
+
+/// Remove the aliases matching `pattern`, after listing them and asking for confirmation
+/// (unless `yes` or a dry run).
+fn remove_matching(
+    filename: &str, (pattern, re): (&str, Regex), force: bool, yes: bool, preview: &Preview,
+) -> Result<()> {
+    let aliases_file = load_aliases_file(filename, force)?.with_preview(preview.clone());
+    let mut matching_aliases: Vec<&str> =
+        present_aliases(&aliases_file).into_iter().filter(|alias| re.is_match(alias)).collect();
+    matching_aliases.sort_unstable();
+    if matching_aliases.is_empty() && aliases_file.all_aliases_are_valid() {
+        log::warn!("No aliases in {} match {:?}", filename, pattern);
+        return Ok(());
+    }
+    if !yes && !preview.dry_run {
+        eprintln!("Aliases matching {:?} in {}:", pattern, filename);
+        for alias in matching_aliases.iter() {
+            eprintln!("  {}", alias);
+        }
+        let question = format!("Remove {} aliases? (y)es or (n)o", matching_aliases.len());
+        if ask(&question, "yn")? == "n" {
+            return Err(anyhow!("removal cancelled"));
+        }
+    }
+    for alias in aliases_file.invalid_aliases().iter() {
+        log::info!("Removing invalid alias {:?} from {}", alias, filename);
+    }
+    for alias in matching_aliases.iter() {
+        log::info!("Removing alias {:?} from {}", alias, filename);
+    }
+    aliases_file.remove(&matching_aliases, false)
+}
+
+fn disable(filename: &str, arg_aliases: &[String], preview: &Preview) -> Result<()> {
+    validate_aliases(arg_aliases)?;
+    let aliases_file = load_aliases_file(filename, false)?.with_preview(preview.clone());
+    let (extant_aliases, missing_aliases) =
+        split_aliases(&aliases_file.aliases().into_iter().collect(), arg_aliases);
+    for alias in missing_aliases.iter() {
+        log::warn!("Alias {:?} is not enabled in {}", alias, filename);
+    }
+    for alias in extant_aliases.iter() {
+        log::info!("Disabling alias {:?} in {}", alias, filename);
+    }
+    aliases_file.disable(&extant_aliases)
+}
+
+fn prune(filename: &str, preview: &Preview) -> Result<()> {
+    let aliases_file = load_aliases_file(filename, true)?.with_preview(preview.clone());
+    let expired_aliases = aliases_file.expired_aliases();
+    if expired_aliases.is_empty() {
+        log::info!("No expired aliases in {}", filename);
+        return Ok(());
+    }
+    for alias in expired_aliases.iter() {
+        log::info!("Removing expired alias {:?} from {}", alias, filename);
+    }
+    aliases_file.prune()
+}
+
+fn rename(
+    filename: &str, old: &str, new: &str, policy: &Policy, preview: &Preview,
+) -> Result<()> {
+    validate_aliases(&[old, new])?;
+    let aliases_file = load_aliases_file(filename, false)?.with_preview(preview.clone());
+    policy.validate_aliases(&[new], &policy::user_groups(), aliases_file.aliases().len())?;
+    log::info!("Renaming alias {:?} to {:?} in {}", old, new, filename);
+    aliases_file.rename(old, new)
+}
+
+fn enable(filename: &str, arg_aliases: &[String], preview: &Preview) -> Result<()> {
+    validate_aliases(arg_aliases)?;
+    let aliases_file = load_aliases_file(filename, false)?.with_preview(preview.clone());
+    let (extant_aliases, missing_aliases) =
+        split_aliases(&aliases_file.disabled_aliases().into_iter().collect(), arg_aliases);
+    for alias in missing_aliases.iter() {
+        log::warn!("Alias {:?} is not disabled in {}", alias, filename);
+    }
+    for alias in extant_aliases.iter() {
+        log::info!("Enabling alias {:?} in {}", alias, filename);
+    }
+    aliases_file.enable(&extant_aliases)
+}
+
+/// List the aliases an alias command (see `completions::ALIAS_COMMANDS`) applies to, one per
+/// line, for the completion scripts. An unreadable file has no aliases to complete.
+fn complete(filename: &str, command: &str) -> Result<()> {
+    let aliases_file = match AliasesFile::from_file(filename, true) {
+        Ok(aliases_file) => aliases_file,
+        Err(_) => return Ok(()),
+    };
+    let aliases = match command {
+        "enable" => aliases_file.disabled_aliases(),
+        "disable" | "status" => aliases_file.aliases(),
+        _ => aliases_file.all_aliases().into_iter().filter_map(|alias| alias.ok()).collect(),
+    };
+    aliases.iter().for_each(|alias| println!("{}", alias));
+    Ok(())
+}
+
+/// List the saved versions of the aliases file (newest first), with who replaced each
+/// version, when, and how (the command, and the lines it added and removed).
+fn history(filename: &str, format: OutputFormat, preview: &Preview) -> Result<()> {
+    let history = preview.history.as_ref().ok_or_else(history_disabled)?;
+    let versions = history.versions(filename)?;
+    // Each version was replaced by the next newer one (or by the current file)
+    let mut newer_text = fs::read_to_string(filename).unwrap_or_default();
+    let mut changes = Vec::<(usize, usize)>::with_capacity(versions.len());
+    for version in versions.iter() {
+        let text = fs::read_to_string(&version.path)
+            .with_context(|| format!("could not read {:?}", version.path.display()))?;
+        let old_lines: Vec<&str> = text.lines().collect();
+        let new_lines: Vec<&str> = newer_text.lines().collect();
+        changes.push(diff::change_counts(&old_lines, &new_lines));
+        newer_text = text;
+    }
+    if format != OutputFormat::Text {
+        let records: Vec<Vec<Value>> = versions
+            .iter()
+            .zip(changes)
+            .zip(1..)
+            .map(|((version, (added, removed)), number)| {
+                vec![
+                    Value::Number(number),
+                    Value::Text(version.timestamp()),
+                    Value::Text(version.user.clone()),
+                    Value::Number(added),
+                    Value::Number(removed),
+                    Value::Text(version.command.clone()),
+                ]
+            })
+            .collect();
+        let columns = ["version", "time", "user", "added", "removed", "command"];
+        print!("{}", output::encode_records(format, &columns, &records));
+        return Ok(());
+    }
+    if versions.is_empty() {
+        log::warn!(r#"No saved versions of "{}""#, filename);
+    }
+    for ((version, (added, removed)), number) in versions.iter().zip(changes).zip(1..) {
+        println!(
+            "{:>3}  {}  {:<10}  +{:<3} -{:<3}  {}",
+            number,
+            version.timestamp(),
+            version.user,
+            added,
+            removed,
+            version.command
+        );
+    }
+    Ok(())
+}
+
+/// Restore a saved version of the aliases file (version 1 is the version before the last
+/// change). The restore is itself saved, so `undo` again reverts it.
+fn undo(filename: &str, version: usize, preview: &Preview) -> Result<()> {
+    let history = preview.history.as_ref().ok_or_else(history_disabled)?;
+    let versions = history.versions(filename)?;
+    let saved = versions.get(version - 1).ok_or_else(|| {
+        anyhow!(r#""{}" has {} saved versions (see history)"#, filename, versions.len())
+    })?;
+    let text = fs::read_to_string(&saved.path)
+        .with_context(|| format!("could not read {:?}", saved.path.display()))?;
+    log::info!(
+        r#"Restoring "{}" as it was before {} ({} by {})"#,
+        filename,
+        saved.timestamp(),
+        saved.command,
+        saved.user
+    );
+    AliasesFile::from_file(filename, true)?.with_preview(preview.clone()).restore(&text)
+}
+
+/// The history of the aliases file: kept in `history_dir`, or (by default) in `HISTORY_DIR`
+/// if it exists (e.g., created by `bin/install-systemd`).
+fn file_history(history_dir: Option<&str>, limit: usize) -> Option<History> {
+    match (history_dir, limit) {
+        (_, 0) => None,
+        (Some(history_dir), _) => Some(History::new(history_dir, limit)),
+        (None, _) => Path::new(HISTORY_DIR).is_dir().then(|| History::new(HISTORY_DIR, limit)),
+    }
+}
+
+fn history_disabled() -> anyhow::Error {
+    anyhow!(
+        "the history is disabled (--history-limit is 0, or {} does not exist and no \
+         --history-dir is given)",
+        HISTORY_DIR
+    )
+}
+
+fn fmt(filename: &str, check: bool, sort: bool, preview: &Preview) -> Result<()> {
+    let aliases_file = AliasesFile::from_file(filename, true)?.with_preview(preview.clone());
+    if aliases_file.is_formatted(sort) {
+        log::info!(r#""{}" is formatted"#, filename);
+        return Ok(());
+    }
+    if check {
+        return Err(anyhow!(r#""{}" is not formatted"#, filename));
+    }
+    log::info!(r#"Formatting "{}""#, filename);
+    aliases_file.format(sort)
+}
+
+fn check(filename: &str, arg_files: &[String], format: OutputFormat) -> Result<i32> {
+    let filenames = match arg_files.is_empty() {
+        true => vec![filename.to_owned()],
+        false => arg_files.to_vec(),
+    };
+    // The host name check needs Avahi; skip it when Avahi is unavailable (e.g., in CI).
+    let host_name = match avahi_host_name() {
+        Ok(host_name) => Some(host_name),
+        Err(error) => {
+            log::info!("Skipping the host name check: {}", error);
+            None
+        },
+    };
+    let mut findings = Vec::<(&str, Finding)>::new();
+    for filename in filenames.iter() {
+        match AliasesFile::from_file(filename, true) {
+            Ok(aliases_file) => {
+                let file_findings =
+                    check::check_lines(aliases_file.lines(), host_name.as_deref());
+                findings.extend(
+                    file_findings.into_iter().map(|finding| (filename.as_str(), finding)),
+                )
+            },
+            Err(error) => findings.push((filename, Finding {
+                line: 0,
+                severity: Severity::Error,
+                check: "unreadable",
+                message: error.to_string(),
+            })),
+        }
+    }
+    match format {
+        OutputFormat::Text => {
+            for (filename, finding) in findings.iter() {
+                println!(
+                    "{}:{}: {}: {} [{}]",
+                    filename, finding.line, finding.severity, finding.message, finding.check
+                );
+            }
+        },
+        format => {
+            let records: Vec<Vec<Value>> = findings
+                .iter()
+                .map(|(filename, finding)| {
+                    vec![
+                        Value::Text(filename.to_string()),
+                        Value::Number(finding.line),
+                        Value::Text(finding.severity.to_string()),
+                        Value::Text(finding.check.to_owned()),
+                        Value::Text(finding.message.clone()),
+                    ]
+                })
+                .collect();
+            let columns = ["file", "line", "severity", "check", "message"];
+            print!("{}", output::encode_records(format, &columns, &records));
+        },
+    }
+    Ok(match findings.iter().map(|(_, finding)| finding.severity).max() {
+        Some(Severity::Error) => EXIT_CHECK_ERRORS,
+        Some(Severity::Warning) => EXIT_CHECK_WARNINGS,
+        None => EXIT_SUCCESS,
+    })
+}
+
+/// Export the local host's aliases in another format. The target and addresses come from
+/// Avahi unless given.
+fn export(
+    filename: &str, format: ExchangeFormat, target: Option<&str>, arg_addresses: &[IpAddr],
+) -> Result<()> {
+    let target = match target {
+        Some(target) => target.trim_end_matches('.').to_owned(),
+        None => avahi_host_name_fqdn()?,
+    };
+    let host_name = target.strip_suffix(".local").unwrap_or(&target);
+    let variables = template::host_variables(host_name);
+    let aliases_file =
+        AliasesFile::from_file(filename, true)?.for_host(host_name).expand_defined(&variables);
+    skip_unpublishable_aliases(&aliases_file);
+    let addresses = match (arg_addresses.is_empty(), format) {
+        (_, ExchangeFormat::Aliases) | (false, _) => arg_addresses.to_vec(),
+        (true, _) => host_addresses(&target)?,
+    };
+    let aliases: Vec<&str> =
+        aliases_file.aliases().into_iter().filter(|a| !template::is_template(a)).collect();
+    print!("{}", interchange::export_aliases(format, &aliases, &target, &addresses));
+    Ok(())
+}
+
+/// Resolve the IPv4 and IPv6 addresses of a host through Avahi.
+fn host_addresses(host_name: &str) -> Result<Vec<IpAddr>> {
+    let dbus_connection = dbus_connection()?;
+    let avahi_server_proxy = avahi_server_proxy(&dbus_connection);
+    let mut addresses: Vec<IpAddr> = [avahi::Protocol::INET, avahi::Protocol::INET6]
+        .into_iter()
+        .filter_map(|protocol| resolve_host_name(&avahi_server_proxy, host_name, protocol).ok())
+        .filter_map(|resolution| resolution.address.parse().ok())
+        .collect();
+    addresses.sort();
+    match addresses.is_empty() {
+        true => Err(anyhow!(
+            "could not resolve {:?} (use --address to give its addresses)",
+            host_name
+        )),
+        false => Ok(addresses),
+    }
+}
+
+/// Import the `.local` names in other files (e.g., `/etc/avahi/hosts` or another
+/// implementation's aliases file) as aliases. Names already in the aliases file are skipped,
+/// and invalid ones are reported and skipped.
+fn import(
+    filename: &str, files: &[String], format: ExchangeFormat, group: Option<&str>,
+    policy: &Policy, preview: &Preview,
+) -> Result<()> {
+    validate_group(group)?;
+    let mut aliases = Vec::<String>::new();
+    for path in import_paths(files)? {
+        let text = fs::read_to_string(&path)
+            .with_context(|| format!("could not read {:?}", path.display()))?;
+        for name in interchange::import_names(format, &text) {
+            match is_valid_alias(&name) {
+                true if !aliases.contains(&name) => aliases.push(name),
+                true => (),
+                false => log::warn!("Skipping invalid alias {:?} ({})", name, path.display()),
+            }
+        }
+    }
+    let aliases_file = load_aliases_file(filename, false)?;
+    check_added_aliases(&aliases_file, policy, &aliases)?;
+    let aliases_file = aliases_file.with_preview(preview.clone());
+    let (_, new_aliases) = split_aliases(&present_aliases(&aliases_file), &aliases);
+    if new_aliases.is_empty() {
+        log::info!("No new aliases to import");
+        return Ok(());
+    }
+    for alias in new_aliases.iter() {
+        log::info!("Adding {:?} to {}", alias, filename);
+    }
+    aliases_file.append_to_group(&new_aliases, group)
+}
+
+/// The files to import: each file, and the files in each directory (e.g., `aliases.d`).
+fn import_paths(files: &[String]) -> Result<Vec<PathBuf>> {
+    let mut paths = Vec::<PathBuf>::new();
+    for file in files.iter().map(PathBuf::from) {
+        if !file.is_dir() {
+            paths.push(file);
+            continue;
+        }
+        let entries = fs::read_dir(&file)
+            .with_context(|| format!("could not read {:?}", file.display()))?;
+        let mut dir_paths = entries
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<io::Result<Vec<PathBuf>>>()?;
+        dir_paths.retain(|path| path.is_file());
+        dir_paths.sort();
+        paths.extend(dir_paths);
+    }
+    Ok(paths)
+}
+
+/// Report the invalid aliases and the templates that cannot be expanded here (their variables
+/// are defined on the daemon command line), which are skipped.
+fn skip_unpublishable_aliases(aliases_file: &AliasesFile) {
+    for alias in aliases_file.invalid_aliases() {
+        log::warn!("Skipping invalid alias {:?}", alias);
+    }
+    for template in aliases_file.unexpanded_templates() {
+        log::info!(
+            "Skipping template {:?} (its variables are defined by the daemon)",
+            template
+        );
+    }
+}
+
+/// Report the network status of the aliases for this host (or of `arg_aliases`).
+fn status(filename: &str, arg_aliases: &[String], format: OutputFormat) -> Result<()> {
+    validate_aliases(arg_aliases)?;
+    let dbus_connection = dbus_connection()?;
+    let avahi_server_proxy = avahi_server_proxy(&dbus_connection);
+    let host_name = avahi_server_proxy.get_host_name()?;
+    let local_host = LocalHost::new(&avahi_server_proxy)?;
+    let variables = template::host_variables(&host_name);
+    let aliases_file =
+        AliasesFile::from_file(filename, true)?.for_host(&host_name).expand_defined(&variables);
+    skip_unpublishable_aliases(&aliases_file);
+    let file_aliases = aliases_file.aliases();
+    for alias in arg_aliases.iter().filter(|alias| !file_aliases.contains(&alias.as_str())) {
+        log::warn!("{} is not an active alias for this host", alias);
+    }
+    let statuses: Vec<AliasStatus> = aliases_file
+        .aliases()
+        .into_iter()
+        .filter(|alias| !template::is_template(alias))
+        .filter(|alias| arg_aliases.is_empty() || arg_aliases.iter().any(|a| a == alias))
+        .map(|alias| alias_status(&avahi_server_proxy, &local_host, alias))
+        .collect();
+    if format != OutputFormat::Text {
+        let records: Vec<Vec<Value>> = statuses.iter().map(AliasStatus::to_record).collect();
+        let columns = ["alias", "status", "host", "address", "interface", "ms", "error"];
+        print!("{}", output::encode_records(format, &columns, &records));
+        return Ok(());
+    }
+    if statuses.is_empty() {
+        log::warn!(r#"No aliases in "{}""#, filename);
+    }
+    let width = statuses.iter().map(|status| status.alias.len()).max().unwrap_or_default();
+    for status in statuses {
+        println!("{}", status.to_line(width));
+    }
+    Ok(())
+}
+
+/// Check the environment the aliases depend on. Returns `EXIT_DOCTOR_FAILURES` if any check
+/// failed, `EXIT_DOCTOR_WARNINGS` if any check warned, and `EXIT_SUCCESS` otherwise.
+fn doctor(filename: &str, format: OutputFormat) -> Result<i32> {
+    let diagnoses = doctor::diagnose(dbus_connection().as_ref(), filename);
+    if format != OutputFormat::Text {
+        let records: Vec<Vec<Value>> = diagnoses.iter().map(Diagnosis::to_record).collect();
+        let columns = ["check", "status", "message", "fix"];
+        print!("{}", output::encode_records(format, &columns, &records));
+    } else {
+        let width = diagnoses.iter().map(|d| d.check.len()).max().unwrap_or_default();
+        for diagnosis in diagnoses.iter() {
+            let health = diagnosis.health.name().to_uppercase();
+            println!(
+                "{}  {:width$}  {}",
+                health,
+                diagnosis.check,
+                diagnosis.message,
+                width = width
+            );
+            if let Some(fix) = &diagnosis.fix {
+                println!("{:indent$}  fix: {}", "", fix, indent = health.len() + width + 2);
+            }
+        }
+    }
+    Ok(match diagnoses.iter().map(|d| d.health).max() {
+        Some(Health::Fail) => EXIT_DOCTOR_FAILURES,
+        Some(Health::Warn) => EXIT_DOCTOR_WARNINGS,
+        _ => EXIT_SUCCESS,
+    })
+}
+
+fn resolve(name_or_address: &str, format: OutputFormat) -> Result<()> {
+    let answers = lookup::resolve(&dbus_connection()?, name_or_address)?;
+    if format != OutputFormat::Text {
+        let records: Vec<Vec<Value>> = answers.iter().map(Answer::to_record).collect();
+        let columns = ["name", "type", "value", "interface", "protocol"];
+        print!("{}", output::encode_records(format, &columns, &records));
+        return Ok(());
+    }
+    for answer in answers {
+        println!("{}", answer.to_line());
+    }
+    Ok(())
+}
+
+fn sync(
+    filename: &str, arg_aliases: &[String], from: Option<&str>, keep_extra: bool,
+    policy: &Policy, preview: &Preview,
+) -> Result<i32> {
+    let desired_aliases = collect_aliases(arg_aliases, from, false)?;
+    let aliases_file = load_aliases_file(filename, false)?.with_preview(preview.clone());
+    let desired: HashSet<&str> = desired_aliases.iter().map(String::as_str).collect();
+    let disabled_aliases = aliases_file.disabled_aliases().into_iter().collect();
+    let (disabled_aliases, _) = split_aliases(&disabled_aliases, &desired_aliases);
+    let present = aliases_file.present_aliases().into_iter().collect();
+    let (_, missing_aliases) = split_aliases(&present, &desired_aliases);
+    let extra_aliases: Vec<&str> = match keep_extra {
+        true => vec![],
+        false => present_aliases(&aliases_file)
+            .into_iter()
+            .filter(|a| !desired.contains(a) && !template::is_template(a))
+            .collect(),
+    };
+    if disabled_aliases.is_empty() && missing_aliases.is_empty() && extra_aliases.is_empty() {
+        log::info!(r#""{}" is in sync"#, filename);
+        return Ok(EXIT_SUCCESS);
+    }
+    let published_aliases: Vec<&str> =
+        missing_aliases.iter().chain(disabled_aliases.iter()).copied().collect();
+    let alias_count = match keep_extra {
+        true => aliases_file.aliases().len() + published_aliases.len(),
+        false => desired_aliases.len(),
+    };
+    policy.validate_aliases(&published_aliases, &policy::user_groups(), alias_count)?;
+    for alias in disabled_aliases.iter() {
+        log::info!("Enabling alias {:?} in {}", alias, filename);
+    }
+    for alias in extra_aliases.iter() {
+        log::info!("Removing alias {:?} from {}", alias, filename);
+    }
+    for alias in missing_aliases.iter() {
+        log::info!("Adding {:?} to {}", alias, filename);
+    }
+    let desired_aliases: Vec<&str> = desired_aliases.iter().map(String::as_str).collect();
+    aliases_file.sync(&desired_aliases, keep_extra)?;
+    // A dry run changes nothing
+    match preview.dry_run {
+        true => Ok(EXIT_SUCCESS),
+        false => Ok(EXIT_SYNC_CHANGED),
+    }
+}
+
+/// Collect the command line aliases. An alias of `-` reads aliases from standard input, and
+/// `from` names a file of aliases (see `read_aliases`). Every alias is validated (and every
+/// invalid alias reported) before any is used; alias templates are accepted only if
+/// `allow_templates` (i.e., for aliases added to the file). Duplicates are dropped.
+fn collect_aliases(
+    arg_aliases: &[String], from: Option<&str>, allow_templates: bool,
+) -> Result<Vec<String>> {
+    let new_alias = match allow_templates {
+        true => new_alias_or_template,
+        false => new_alias,
+    };
+    let mut aliases = Vec::<String>::new();
+    let mut invalid_aliases = Vec::<String>::new();
+    let stdin = arg_aliases.iter().any(|alias| alias == "-").then_some("-");
+    for source in stdin.into_iter().chain(from) {
+        let (valid, invalid) = read_aliases(source)?;
+        let (valid, templates): (Vec<String>, Vec<String>) =
+            valid.into_iter().partition(|alias| new_alias(alias).is_ok());
+        aliases.extend(valid);
+        invalid_aliases.extend(templates.iter().map(|alias| format!("{:?}", alias)));
+        invalid_aliases.extend(invalid);
+    }
+    for alias in arg_aliases.iter().filter(|a| *a != "-") {
+        match new_alias(alias) {
+            Ok(_) => aliases.push(alias.clone()),
+            Err(_) => invalid_aliases.push(format!("{:?}", alias)),
+        }
+    }
+    if !invalid_aliases.is_empty() {
+        return Err(anyhow!("invalid aliases: {}", invalid_aliases.join(", ")));
+    }
+    let mut seen = HashSet::<String>::new();
+    aliases.retain(|alias| seen.insert(alias.clone()));
+    Ok(aliases)
+}
+
+/// Read aliases, one per line, from a file (or standard input if `source` is `-`) using the
+/// aliases file syntax: comments and blank lines are ignored. Returns the valid aliases and
+/// descriptions of the invalid aliases.
+fn read_aliases(source: &str) -> Result<(Vec<String>, Vec<String>)> {
+    let (name, text) = match source {
+        "-" => (
+            "stdin",
+            io::read_to_string(io::stdin()).with_context(|| "could not read aliases")?,
+        ),
+        _ => (
+            source,
+            fs::read_to_string(source)
+                .with_context(|| format!(r#"could not read aliases from "{}""#, source))?,
+        ),
+    };
+    let mut aliases = Vec::<String>::new();
+    let mut invalid_aliases = Vec::<String>::new();
+    for (line, number) in text.lines().map(|text| Line::new(text.to_owned())).zip(1..) {
+        match line.alias() {
+            Some(Ok(alias)) => aliases.push(alias.to_owned()),
+            Some(Err(alias)) => {
+                invalid_aliases.push(format!("{:?} ({}:{})", alias, name, number))
+            },
+            None => (),
+        }
+    }
+    Ok((aliases, invalid_aliases))
+}
+
+fn edit(filename: &str, preview: &Preview) -> Result<()> {
+    let original = fs::read_to_string(filename)
+        .with_context(|| format!(r#"could not read "{}""#, filename))?;
+    let edit_file = EditFile::new(filename, &original)?;
+    // The text that is checked is the text that is written
+    let edited = loop {
+        run_editor(&edit_file.path)?;
+        let edited = fs::read_to_string(&edit_file.path)
+            .with_context(|| format!(r#"could not read "{}""#, edit_file.path.display()))?;
+        let lines: Vec<Line> = edited.lines().map(|text| Line::new(text.to_owned())).collect();
+        let findings = check::check_lines(&lines, None);
+        for finding in findings.iter() {
+            eprintln!(
+                "{}:{}: {}: {}",
+                filename, finding.line, finding.severity, finding.message
+            );
+        }
+        if findings.iter().all(|finding| finding.severity < Severity::Error) {
+            break edited;
+        }
+        if ask("What now? (e)dit again or (a)bort", "ea")? == "a" {
+            return Err(anyhow!(r#"edit aborted; "{}" was not changed"#, filename));
+        }
+    };
+    if edited == original {
+        log::info!(r#""{}" was not changed"#, filename);
+        return Ok(());
+    }
+    // The aliases file is rewritten in place of copying the edited file over it, which keeps
+    // its mode and owner (and follows a symbolic link).
+    AliasesFile::from_file(filename, true)?.with_preview(preview.clone()).restore(&edited)?;
+    if !preview.dry_run {
+        log::info!(r#"Updated "{}""#, filename);
+    }
+    Ok(())
+}
+
+/// A temporary copy of a file being edited, in a private directory (removed when dropped)
+struct EditFile {
+    dir: PathBuf,
+    path: PathBuf,
+}
+
+impl EditFile {
+    /// Copy `text` (the content of `filename`) to a new file only the user can access. The
+    /// directory is created by `mkdtemp`, so its name cannot be predicted (or planted).
+    fn new(filename: &str, text: &str) -> Result<Self> {
+        let template = env::temp_dir().join("avahi-alias.XXXXXX");
+        let mut template = CString::new(template.as_os_str().as_bytes())?.into_bytes_with_nul();
+        // SAFETY: template is a writable, NUL-terminated string ending in "XXXXXX".
+        if unsafe { libc::mkdtemp(template.as_mut_ptr().cast()) }.is_null() {
+            return Err(io::Error::last_os_error())
+                .with_context(|| "could not create a directory for editing");
+        }
+        template.pop();
+        let dir = PathBuf::from(OsString::from_vec(template));
+        let name = Path::new(filename).file_name().unwrap_or_default();
+        let edit_file = Self { path: dir.join(name), dir };
+        let mut file = fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&edit_file.path)
+            .with_context(|| format!(r#"could not copy "{}" for editing"#, filename))?;
+        file.write_all(text.as_bytes())
+            .with_context(|| format!(r#"could not copy "{}" for editing"#, filename))?;
+        Ok(edit_file)
+    }
+}
+
+impl Drop for EditFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+        let _ = fs::remove_dir(&self.dir);
+    }
+}
+
+/// Run the user's editor (`$VISUAL`, `$EDITOR`, or `vi`) on a file.
+fn run_editor(path: &Path) -> Result<()> {
+    let editor = ["VISUAL", "EDITOR"]
+        .iter()
+        .find_map(|name| env::var(name).ok().filter(|editor| !editor.trim().is_empty()))
+        .unwrap_or_else(|| String::from("vi"));
+    // The editor may include arguments (e.g., "code --wait")
+    let status = process::Command::new("sh")
+        .arg("-c")
+        .arg(format!(r#"{} "$1""#, editor))
+        .arg("sh")
+        .arg(path)
+        .status()
+        .with_context(|| format!("could not run {:?}", editor))?;
+    match status.success() {
+        true => Ok(()),
+        false => Err(anyhow!("{:?} failed ({})", editor, status)),
+    }
+}
+
+/// Ask a question on the terminal until the answer is one of `choices` (one letter each).
+fn ask(question: &str, choices: &str) -> Result<String> {
+    loop {
+        eprint!("{} ", question);
+        io::stderr().flush()?;
+        let mut answer = String::new();
+        if io::stdin().read_line(&mut answer)? == 0 {
+            return Err(anyhow!("no answer"));
+        }
+        let answer = answer.trim().to_lowercase();
+        if answer.len() == 1 && choices.contains(&answer) {
+            return Ok(answer);
+        }
+    }
+}
+
+fn list_groups(filename: &str) -> Result<()> {
+    let aliases_file = AliasesFile::from_file(filename, true)?;
+    let groups = aliases_file.groups();
+    if groups.is_empty() {
+        log::warn!(r#"No groups in "{}""#, filename);
+    }
+    for (group, enabled) in groups {
+        match enabled {
+            true => println!("{}", group),
+            false => println!("{} (disabled)", group),
+        }
+    }
+    Ok(())
+}
+
+fn enable_groups(filename: &str, arg_groups: &[String], preview: &Preview) -> Result<()> {
+    let aliases_file = AliasesFile::from_file(filename, false)?.with_preview(preview.clone());
+    let groups = split_groups(&aliases_file, arg_groups, false, filename);
+    for group in groups.iter() {
+        log::info!("Enabling group {:?} in {}", group, filename);
+    }
+    aliases_file.enable_groups(&groups)
+}
+
+fn disable_groups(filename: &str, arg_groups: &[String], preview: &Preview) -> Result<()> {
+    let aliases_file = AliasesFile::from_file(filename, false)?.with_preview(preview.clone());
+    let groups = split_groups(&aliases_file, arg_groups, true, filename);
+    for group in groups.iter() {
+        log::info!("Disabling group {:?} in {}", group, filename);
+    }
+    aliases_file.disable_groups(&groups)
+}
+
+/// Return the command line groups currently in the `enabled` state (warning about the rest).
+fn split_groups<'a>(
+    aliases_file: &AliasesFile, arg_groups: &'a [String], enabled: bool, filename: &str,
+) -> Vec<&'a str> {
+    let file_groups = aliases_file.groups();
+    let state = if enabled { "enabled" } else { "disabled" };
+    arg_groups
+        .iter()
+        .map(|group| group.as_str())
+        .filter(|group| match file_groups.iter().find(|(g, _)| g == group) {
+            Some((_, e)) if *e == enabled => true,
+            Some(_) => {
+                log::warn!("Group {:?} is not {} in {}", group, state, filename);
+                false
+            },
+            None => {
+                log::warn!("Group {:?} is not in {}", group, filename);
+                false
+            },
+        })
+        .collect()
+}
+
+/// Load the aliases file. If the file contains host selectors, the aliases are restricted
+/// to those applying to the local host (as named by Avahi).
+fn load_aliases_file(filename: &str, allow_invalid: bool) -> Result<AliasesFile> {
+    let aliases_file = AliasesFile::from_file(filename, allow_invalid)?;
+    match aliases_file.has_selectors() {
+        true => Ok(aliases_file.for_host(&avahi_host_name()?)),
+        false => Ok(aliases_file),
+    }
+}
+
+/// Get the local host name from Avahi.
+fn avahi_host_name() -> Result<String> {
+    let dbus_connection = dbus_connection()?;
+    avahi_server_proxy(&dbus_connection)
+        .get_host_name()
+        .with_context(|| "could not get host name from Avahi")
+}
+
+/// Get the local host's fully qualified name (e.g., `gandalf.local`) from Avahi.
+fn avahi_host_name_fqdn() -> Result<String> {
+    let dbus_connection = dbus_connection()?;
+    avahi_server_proxy(&dbus_connection)
+        .get_host_name_fqdn()
+        .with_context(|| "could not get host name from Avahi")
+}
+
+/// How often `publish` and `run` check that Avahi is still publishing the aliases
+const PUBLISH_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Publish aliases until interrupted (e.g., by Ctrl-C). The entry group belongs to this
+/// process, so Avahi withdraws the aliases when it exits.
+fn publish(arg_aliases: &[String]) -> Result<()> {
+    let dbus_connection = dbus_connection()?;
+    let avahi_server_proxy = avahi_server_proxy(&dbus_connection);
+    let mut published_group = publish_aliases(&avahi_server_proxy, arg_aliases)?;
+    eprintln!("Publishing {} (press Ctrl-C to stop)", arg_aliases.join(", "));
+    loop {
+        thread::sleep(PUBLISH_CHECK_INTERVAL);
+        check_groups(slice::from_mut(&mut published_group));
+        if published_group.is_withdrawn() {
+            return Err(anyhow!("Avahi withdrew the aliases"));
+        }
+    }
+}
+
+/// Publish aliases while a command runs, and return its exit status. (A command killed by a
+/// signal returns 128 plus the signal number, like a shell.)
+fn run(arg_aliases: &[String], command: &[String]) -> Result<i32> {
+    let dbus_connection = dbus_connection()?;
+    let avahi_server_proxy = avahi_server_proxy(&dbus_connection);
+    let mut published_group = publish_aliases(&avahi_server_proxy, arg_aliases)?;
+    let mut child = process::Command::new(&command[0])
+        .args(&command[1..])
+        .spawn()
+        .with_context(|| format!("could not run {:?}", command[0]))?;
+    // Ctrl-C also interrupts the command; wait for it to exit and pass on its status.
+    ignore_interrupts();
+    let mut last_check = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if last_check.elapsed() >= PUBLISH_CHECK_INTERVAL {
+            check_groups(slice::from_mut(&mut published_group));
+            last_check = Instant::now();
+        }
+        thread::sleep(Duration::from_millis(100));
+    };
+    withdraw_groups(slice::from_ref(&published_group));
+    Ok(status.code().unwrap_or_else(|| 128 + status.signal().unwrap_or_default()))
+}
+
+/// Publish aliases (or templates, expanded for the local host) in a new entry group.
+fn publish_aliases<'c>(
+    avahi_server_proxy: &avahi_dbus::DBusProxy<'_, 'c>, arg_aliases: &[String],
+) -> Result<PublishedGroup<'c>> {
+    validate_aliases_or_templates(arg_aliases)?;
+    let variables = template::host_variables(&avahi_server_proxy.get_host_name()?);
+    let mut aliases = Vec::<String>::with_capacity(arg_aliases.len());
+    for alias in arg_aliases {
+        let alias = template::expand(alias, &variables)?;
+        if !is_valid_alias(&alias) {
+            return Err(anyhow!(r#"invalid alias: "{}""#, alias));
+        }
+        aliases.push(alias);
+    }
+    let rdata = encoding::encode_rdata(&avahi_server_proxy.get_host_name_fqdn()?);
+    let aliases: Vec<&str> = aliases.iter().map(String::as_str).collect();
+    publish_group(avahi_server_proxy, "published aliases", &aliases, &rdata)
+}
+
+/// Ignore the interrupt (Ctrl-C) and quit signals.
+fn ignore_interrupts() {
+    // SAFETY: SIG_IGN installs no handler code; this only changes the signal dispositions.
+    unsafe {
+        libc::signal(libc::SIGINT, libc::SIG_IGN);
+        libc::signal(libc::SIGQUIT, libc::SIG_IGN);
+    }
+}
+
+fn dbus_connection() -> Result<dbus::blocking::Connection> {
+    dbus::blocking::Connection::new_system()
+        .with_context(|| "failed to open D-Bus connection to Avahi service")
+}
+
+fn avahi_server_proxy(
+    dbus_connection: &dbus::blocking::Connection,
+) -> avahi_dbus::DBusProxy<'_, '_> {
+    dbus_connection.with_proxy(
+        avahi::AVAHI_DBUS_NAME,
+        avahi::AVAHI_DBUS_PATH_SERVER,
+        avahi_aliases::DEFAULT_TIMEOUT,
+    )
+}
+
+/// The set of valid aliases in the file, enabled or disabled.
+fn present_aliases(aliases_file: &AliasesFile) -> HashSet<&str> {
+    aliases_file.all_aliases().into_iter().filter_map(|alias| alias.ok()).collect()
+}
+
 fn split_aliases<'a>(
     file_aliases: &HashSet<&str>, arg_aliases: &'a [String],
 ) -> (Vec<&'a str>, Vec<&'a str>) {
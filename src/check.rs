@@ -0,0 +1,253 @@
+//! Aliases file checks
+//!
+//! Check the lines of an aliases file for problems (invalid aliases, duplicates, etc.)
+//! without changing the file. Each problem is reported as a `Finding` with a severity:
+//! errors are aliases the daemon cannot publish; warnings are probably mistakes.
+
+#![warn(clippy::all)]
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::alias::is_valid_alias;
+use crate::expiry::{self, EXPIRES_ATTRIBUTE};
+use crate::template::{self, is_template};
+use crate::Line;
+
+/// The maximum length of a DNS label (in octets)
+pub const MAX_LABEL_LENGTH: usize = 63;
+
+/// The severity of a finding
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        })
+    }
+}
+
+/// A problem found in an aliases file
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Finding {
+    /// The line number (1-based)
+    pub line: usize, // cov(skip)
+    pub severity: Severity, // cov(skip)
+    /// The name of the check (e.g., `duplicate-alias`)
+    pub check: &'static str, // cov(skip)
+    pub message: String,    // cov(skip)
+}
+
+impl Finding {
+    fn new(line: usize, severity: Severity, check: &'static str, message: String) -> Self {
+        Self { line, severity, check, message }
+    }
+}
+
+/// Check the lines of an aliases file. If `host_name` is given, aliases equal to the host's
+/// own name are reported.
+///
+/// Note: the lines must not be expanded (see `Line::expand`).
+pub fn check_lines(lines: &[Line], host_name: Option<&str>) -> Vec<Finding> {
+    let variables = host_name.map(template::host_variables);
+    let mut findings = Vec::<Finding>::new();
+    let mut groups = HashMap::<&str, usize>::new();
+    let mut aliases = HashMap::<(String, Vec<&str>), usize>::new();
+    let mut header: Option<&Line> = None;
+    for (line, number) in lines.iter().zip(1..) {
+        if let Some(group) = line.group() {
+            header = Some(line);
+            if let Some(first) = groups.insert(group, number) {
+                groups.insert(group, first);
+                let message = format!(r#"group "{}" is also defined on line {}"#, group, first);
+                findings.push(Finding::new(
+                    number,
+                    Severity::Warning,
+                    "duplicate-group",
+                    message,
+                ));
+            }
+            continue;
+        }
+        let alias = match line.alias() {
+            Some(Ok(alias)) => alias,
+            Some(Err(alias)) => {
+                findings.push(check_invalid_alias(number, alias));
+                continue;
+            },
+            None => continue,
+        };
+        if let Some(finding) = check_label_lengths(number, alias) {
+            findings.push(finding);
+        }
+        if let Some(expires) = line.attribute(EXPIRES_ATTRIBUTE) {
+            if expiry::parse_timestamp(expires).is_none() {
+                let message =
+                    format!(r#"invalid expiry "{}" (expected an RFC 3339 timestamp)"#, expires);
+                findings.push(Finding::new(number, Severity::Error, "invalid-expiry", message));
+            }
+        }
+        if line.is_disabled() || header.is_some_and(Line::is_disabled) {
+            continue;
+        }
+        // Duplicates are aliases published twice on the same hosts
+        let mut selectors: Vec<&str> = header
+            .map_or(vec![], Line::selectors)
+            .into_iter()
+            .chain(line.selectors())
+            .collect();
+        selectors.sort_unstable();
+        if let Some(first) = aliases.get(&(alias.to_owned(), selectors.clone())) {
+            let message = format!(r#""{}" is also published on line {}"#, alias, first);
+            findings.push(Finding::new(number, Severity::Warning, "duplicate-alias", message));
+        } else {
+            aliases.insert((alias.to_owned(), selectors), number);
+        }
+        if let (Some(host_name), Some(variables)) = (host_name, &variables) {
+            let expanded = template::expand(alias, variables).unwrap_or_default();
+            if expanded.eq_ignore_ascii_case(&format!("{}.local", host_name)) {
+                let message = format!(r#""{}" is the host's own name"#, alias);
+                findings.push(Finding::new(number, Severity::Error, "host-name", message));
+            }
+        }
+    }
+    findings
+}
+
+/// Explain why an alias is invalid.
+fn check_invalid_alias(number: usize, alias: &str) -> Finding {
+    if !alias.is_ascii() {
+        let message = format!(
+            r#""{}" contains non-ASCII characters (internationalized names are not supported)"#,
+            alias
+        );
+        return Finding::new(number, Severity::Error, "non-ascii", message);
+    }
+    if let Some(finding) = check_label_lengths(number, alias) {
+        return finding;
+    }
+    if is_template(alias) {
+        let message = format!(r#""{}" is not a valid alias template"#, alias);
+        return Finding::new(number, Severity::Error, "invalid-template", message);
+    }
+    let name = alias.strip_suffix(".local");
+    let reason = match name {
+        None => "aliases must end in .local",
+        Some("") => "the name before .local is empty",
+        Some(_) if is_valid_alias(&alias.to_ascii_lowercase()) => "aliases must be lowercase",
+        Some(name) if name.contains('.') => "aliases must have one label before .local",
+        Some(name) if name.starts_with('-') || name.ends_with('-') => {
+            "aliases must start and end with a letter or digit"
+        },
+        Some(_) => "aliases may only contain letters, digits, and hyphens",
+    };
+    let message = format!(r#"invalid alias "{}": {}"#, alias, reason);
+    Finding::new(number, Severity::Error, "invalid-alias", message)
+}
+
+/// Report labels longer than DNS allows. (Template placeholders are not counted.)
+fn check_label_lengths(number: usize, alias: &str) -> Option<Finding> {
+    let label = alias.split('.').find(|label| match is_template(label) {
+        true => false,
+        false => label.len() > MAX_LABEL_LENGTH,
+    })?;
+    let message = format!(
+        r#"label "{}" is {} octets long (the maximum is {})"#,
+        label,
+        label.len(),
+        MAX_LABEL_LENGTH
+    );
+    Some(Finding::new(number, Severity::Error, "label-too-long", message))
+}
+
+//**********************************************************************************************
+// Unit tests
+//**********************************************************************************************
+
+#[cfg(test)]
+mod tests {
+    use super::{check_lines, Severity};
+    use crate::Line;
+
+    fn check(text: &[&str], host_name: Option<&str>) -> Vec<(usize, Severity, &'static str)> {
+        let lines: Vec<Line> = text.iter().map(|t| Line::new(t.to_string())).collect();
+        check_lines(&lines, host_name).iter().map(|f| (f.line, f.severity, f.check)).collect()
+    }
+
+    #[test]
+    fn valid_files_have_no_findings() {
+        let text = ["# Comment", "", "a.local # A", "!b.local", "[web] @web-*", "c.local @x"];
+        assert!(check(&text, Some("host")).is_empty());
+    }
+
+    #[test]
+    fn invalid_aliases_are_errors() {
+        let text = ["a", "A.local", "a.b.local", "-a.local", "a_b.local", "{x.local"];
+        let findings = check(&text, None);
+        let checks: Vec<_> = findings.iter().map(|(_, s, c)| (*s, *c)).collect();
+        assert_eq!(checks, vec![
+            (Severity::Error, "invalid-alias"),
+            (Severity::Error, "invalid-alias"),
+            (Severity::Error, "invalid-alias"),
+            (Severity::Error, "invalid-alias"),
+            (Severity::Error, "invalid-alias"),
+            (Severity::Error, "invalid-template"),
+        ]);
+        assert_eq!(findings.iter().map(|(l, ..)| *l).collect::<Vec<_>>(), vec![
+            1, 2, 3, 4, 5, 6
+        ]);
+    }
+
+    #[test]
+    fn invalid_alias_messages_explain_the_problem() {
+        let lines = [Line::new(String::from("Media.local")), Line::new(String::from("a.lan"))];
+        let findings = check_lines(&lines, None);
+        assert!(findings[0].message.contains("lowercase"));
+        assert!(findings[1].message.contains(".local"));
+    }
+
+    #[test]
+    fn non_ascii_aliases_are_errors() {
+        assert_eq!(check(&["café.local"], None), vec![(1, Severity::Error, "non-ascii")]);
+    }
+
+    #[test]
+    fn long_labels_are_errors() {
+        let long = format!("{}.local", "a".repeat(64));
+        let ok = format!("{}.local", "a".repeat(63));
+        assert_eq!(check(&[&long, &ok], None), vec![(1, Severity::Error, "label-too-long")]);
+    }
+
+    #[test]
+    fn duplicates_are_warnings() {
+        let text = ["a.local", "[web]", "a.local # again", "a.local @x", "!a.local", "[web]"];
+        assert_eq!(check(&text, None), vec![
+            (3, Severity::Warning, "duplicate-alias"),
+            (6, Severity::Warning, "duplicate-group"),
+        ]);
+    }
+
+    #[test]
+    fn invalid_expiries_are_errors() {
+        let text = ["a.local # expires=2026-10-18T16:00:00Z", "b.local # expires=2h"];
+        assert_eq!(check(&text, None), vec![(2, Severity::Error, "invalid-expiry")]);
+    }
+
+    #[test]
+    fn the_host_name_is_an_error() {
+        let text = ["host.local", "{shorthostname}.local", "other.local"];
+        assert_eq!(check(&text, Some("Host")), vec![
+            (1, Severity::Error, "host-name"),
+            (2, Severity::Error, "host-name"),
+        ]);
+        assert!(check(&text, None).is_empty());
+    }
+}
+
+// end
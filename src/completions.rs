@@ -0,0 +1,219 @@
+//! Shell completions
+//!
+//! Completion scripts are generated from `CommandOpts` (by clap), with additions that complete
+//! the arguments of alias commands by calling `avahi-alias __complete COMMAND`, which lists the
+//! aliases in the aliases file (the one given by `--file`, if any). Aliases are completed only
+//! for positional arguments, not for the values of options (e.g., `remove --from FILE`).
+
+#![warn(clippy::all)]
+
+use anyhow::{anyhow, Result};
+use structopt::clap::{App, AppSettings};
+pub use structopt::clap::Shell;
+use structopt::StructOpt;
+
+use crate::CommandOpts;
+
+/// The name completions are generated for
+const BIN_NAME: &str = "avahi-alias";
+
+/// The commands whose arguments are aliases (`enable` completes disabled aliases, `disable`
+/// and `status` active ones, and the others all aliases)
+pub const ALIAS_COMMANDS: [&str; 5] = ["remove", "rename", "disable", "enable", "status"];
+
+/// The shells completions are generated for
+pub const SHELLS: [&str; 3] = ["bash", "zsh", "fish"];
+
+/// Bash: complete aliases for the alias commands, and use the generated completions otherwise.
+const BASH_ALIASES: &str = r#"
+_avahi-alias_aliases() {
+    local i file cmd
+    for (( i = 1; i < COMP_CWORD; i++ )); do
+        case "${COMP_WORDS[i]}" in
+            -f|--file) file="${COMP_WORDS[i+1]}" ;;
+            COMMANDS) [[ -z "$cmd" ]] && cmd="${COMP_WORDS[i]}" ;;
+        esac
+    done
+    case "${COMP_WORDS[COMP_CWORD-1]}" in
+        OPTIONS) cmd= ;;
+    esac
+    if [[ -n "$cmd" && "${COMP_WORDS[COMP_CWORD]}" != -* ]]; then
+        local aliases
+        aliases="$(avahi-alias ${file:+--file "$file"} __complete "$cmd" 2>/dev/null)"
+        COMPREPLY=( $(compgen -W "$aliases" -- "${COMP_WORDS[COMP_CWORD]}") )
+        return 0
+    fi
+    _avahi-alias "$@"
+}
+
+complete -F _avahi-alias_aliases -o bashdefault -o default avahi-alias
+"#;
+
+/// Zsh: the generated `_avahi-alias` function is renamed, and this one calls it.
+const ZSH_ALIASES: &str = r#"_avahi-alias() {
+    local i file cmd
+    for (( i = 2; i < CURRENT; i++ )); do
+        case "${words[i]}" in
+            -f|--file) file="${words[i+1]}" ;;
+            COMMANDS) [[ -z "$cmd" ]] && cmd="${words[i]}" ;;
+        esac
+    done
+    case "${words[CURRENT-1]}" in
+        OPTIONS) cmd= ;;
+    esac
+    if [[ -n "$cmd" && "${words[CURRENT]}" != -* ]]; then
+        local -a aliases
+        aliases=(${(f)"$(avahi-alias ${file:+--file "$file"} __complete "$cmd" 2>/dev/null)"})
+        compadd -a aliases
+        return
+    fi
+    _avahi-alias_generated "$@"
+}
+
+_avahi-alias "$@"
+"#;
+
+/// Fish: complete aliases for the alias commands.
+const FISH_ALIASES: &str = r#"
+function __avahi_alias_positional
+    not contains -- (commandline -opc)[-1] OPTIONS
+end
+
+function __avahi_alias_aliases
+    set -l words (commandline -opc)
+    set -l file
+    for i in (seq (count $words))
+        if contains -- $words[$i] -f --file; and test $i -lt (count $words)
+            set file --file $words[(math $i + 1)]
+        end
+    end
+    avahi-alias $file __complete $argv 2>/dev/null
+end
+"#;
+
+/// The options that take a value, for the shell's syntax (`|`-separated patterns, or
+/// space-separated words for fish).
+fn value_options(app: &App<'_, '_>, separator: &str) -> String {
+    let commands = app
+        .p
+        .subcommands
+        .iter()
+        .filter(|command| ALIAS_COMMANDS.contains(&command.p.meta.name.as_str()));
+    let mut options: Vec<String> = Some(app)
+        .into_iter()
+        .chain(commands)
+        .flat_map(|app| app.p.opts.iter())
+        .flat_map(|opt| {
+            let short = opt.s.short.map(|short| format!("-{}", short));
+            let long = opt.s.long.map(|long| format!("--{}", long));
+            short.into_iter().chain(long)
+        })
+        .collect();
+    options.sort();
+    options.dedup();
+    options.join(separator)
+}
+
+/// Generate the completion script for a shell (bash, zsh, or fish).
+pub fn completions(shell: Shell) -> Result<String> {
+    // Hidden commands (e.g., `__complete`) are not completed. (clap 2 has no API to remove a
+    // subcommand, and its bash generator cannot handle names containing `__`.)
+    let mut app = CommandOpts::clap();
+    app.p.subcommands.retain(|command| !command.p.is_set(AppSettings::Hidden));
+    let mut script = Vec::<u8>::new();
+    app.gen_completions_to(BIN_NAME, shell, &mut script);
+    let script = String::from_utf8(script)?;
+    let options = value_options(&app, if matches!(shell, Shell::Fish) { " " } else { "|" });
+    match shell {
+        Shell::Bash => Ok(script
+            + &BASH_ALIASES
+                .replace("COMMANDS", &ALIAS_COMMANDS.join("|"))
+                .replace("OPTIONS", &options)),
+        Shell::Zsh => {
+            let (script, _) = script
+                .rsplit_once("_avahi-alias \"$@\"")
+                .ok_or_else(|| anyhow!("unexpected zsh completion script"))?;
+            Ok(script.replacen("\n_avahi-alias() {", "\n_avahi-alias_generated() {", 1)
+                + &ZSH_ALIASES
+                    .replace("COMMANDS", &ALIAS_COMMANDS.join("|"))
+                    .replace("OPTIONS", &options))
+        },
+        Shell::Fish => {
+            let commands = ALIAS_COMMANDS.iter().map(|command| {
+                let condition = format!(
+                    "__fish_seen_subcommand_from {}; and __avahi_alias_positional",
+                    command
+                );
+                format!(
+                    "complete -c {} -n \"{}\" -f -a \"(__avahi_alias_aliases {})\"\n",
+                    BIN_NAME, condition, command
+                )
+            });
+            Ok(script
+                + &FISH_ALIASES.replace("OPTIONS", &options)
+                + &commands.collect::<String>())
+        },
+        _ => Err(anyhow!("completions for {} are not supported", shell)),
+    }
+}
+
+//**********************************************************************************************
+// Unit tests
+//**********************************************************************************************
+
+#[cfg(test)]
+mod tests {
+    use structopt::StructOpt;
+
+    use super::{completions, value_options, Shell, SHELLS};
+    use crate::CommandOpts;
+
+    #[test]
+    fn completions_are_generated_for_each_shell() {
+        for name in SHELLS {
+            let script = completions(name.parse::<Shell>().unwrap()).unwrap();
+            assert!(script.contains("rename"), "{}", name);
+            // Only the alias completions call the hidden command
+            assert_eq!(script.matches("__complete").count(), 1, "{}", name);
+            assert!(!script.contains("COMMANDS"), "{}", name);
+            assert!(!script.contains("OPTIONS"), "{}", name);
+        }
+        assert!(completions(Shell::PowerShell).is_err());
+    }
+
+    #[test]
+    fn zsh_completions_call_the_generated_function() {
+        let script = completions(Shell::Zsh).unwrap();
+        assert_eq!(script.matches("\n_avahi-alias() {").count(), 1);
+        assert_eq!(script.matches("\n_avahi-alias_generated() {").count(), 1);
+        assert!(script.ends_with("_avahi-alias \"$@\"\n"));
+    }
+
+    #[test]
+    fn aliases_are_not_completed_for_option_values() {
+        let options = value_options(&CommandOpts::clap(), "|");
+        assert!(options.split('|').any(|option| option == "--from"), "{}", options);
+        assert!(options.split('|').any(|option| option == "-f"), "{}", options);
+        assert!(!options.split('|').any(|option| option == "--dry-run"), "{}", options);
+        for shell in [Shell::Bash, Shell::Zsh] {
+            assert!(completions(shell)
+                .unwrap()
+                .contains(&format!("        {}) cmd= ;;", options)));
+        }
+        let script = completions(Shell::Fish).unwrap();
+        assert!(script.contains("from status; and __avahi_alias_positional\""));
+    }
+
+    #[test]
+    fn bash_completions_replace_the_generated_registration() {
+        let script = completions(Shell::Bash).unwrap();
+        assert!(script.contains("remove|rename|disable|enable|status)"));
+        let registration = script.lines().rev().find(|line| line.starts_with("complete -F"));
+        assert_eq!(
+            registration,
+            Some("complete -F _avahi-alias_aliases -o bashdefault -o default avahi-alias")
+        );
+    }
+}
+
+// end
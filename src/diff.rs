@@ -0,0 +1,184 @@
+//! Unified diffs
+//!
+//! Line diffs (longest common subsequence) in the unified format used by `diff -u`, for
+//! previewing changes to an aliases file.
+
+#![warn(clippy::all)]
+
+/// The number of unchanged lines shown around each change
+const CONTEXT: usize = 3;
+
+/// An edit operation at an old line index and a new line index
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Op {
+    Equal(usize, usize),
+    Delete(usize, usize),
+    Insert(usize, usize),
+}
+
+impl Op {
+    fn position(&self) -> (usize, usize) {
+        match *self {
+            Op::Equal(i, j) | Op::Delete(i, j) | Op::Insert(i, j) => (i, j),
+        }
+    }
+}
+
+/// Create a unified diff from `old` to `new`. Returns an empty string if the lines are
+/// the same.
+///
+/// # Examples
+///
+/// ```
+/// use avahi_aliases::diff::unified_diff;
+///
+/// let diff = unified_diff("aliases", &["a.local"], &["a.local", "b.local"]);
+/// assert_eq!(diff, "--- aliases\n+++ aliases\n@@ -1 +1,2 @@\n a.local\n+b.local\n");
+/// ```
+pub fn unified_diff(name: &str, old: &[&str], new: &[&str]) -> String {
+    let ops = diff_ops(old, new);
+    let changes: Vec<usize> = ops
+        .iter()
+        .enumerate()
+        .filter(|(_, op)| !matches!(op, Op::Equal(..)))
+        .map(|(i, _)| i)
+        .collect();
+    if changes.is_empty() {
+        return String::new();
+    }
+    let mut diff = format!("--- {}\n+++ {}\n", name, name);
+    // Group the changes into hunks (changes separated by more than twice the context are in
+    // separate hunks).
+    let mut start = 0;
+    while start < changes.len() {
+        let mut end = start;
+        while end + 1 < changes.len() && changes[end + 1] - changes[end] <= 2 * CONTEXT + 1 {
+            end += 1;
+        }
+        let first = changes[start].saturating_sub(CONTEXT);
+        let last = (changes[end] + CONTEXT).min(ops.len() - 1);
+        diff.push_str(&hunk(&ops[first..=last], old, new));
+        start = end + 1;
+    }
+    diff
+}
+
+/// Count the lines added and removed by a change from `old` to `new`.
+pub fn change_counts(old: &[&str], new: &[&str]) -> (usize, usize) {
+    let ops = diff_ops(old, new);
+    let added = ops.iter().filter(|op| matches!(op, Op::Insert(..))).count();
+    let removed = ops.iter().filter(|op| matches!(op, Op::Delete(..))).count();
+    (added, removed)
+}
+
+fn hunk(ops: &[Op], old: &[&str], new: &[&str]) -> String {
+    let (old_start, new_start) = ops[0].position();
+    let old_len = ops.iter().filter(|op| !matches!(op, Op::Insert(..))).count();
+    let new_len = ops.iter().filter(|op| !matches!(op, Op::Delete(..))).count();
+    let mut hunk =
+        format!("@@ -{} +{} @@\n", range(old_start, old_len), range(new_start, new_len));
+    for op in ops {
+        match *op {
+            Op::Equal(i, _) => hunk.push_str(&format!(" {}\n", old[i])),
+            Op::Delete(i, _) => hunk.push_str(&format!("-{}\n", old[i])),
+            Op::Insert(_, j) => hunk.push_str(&format!("+{}\n", new[j])),
+        }
+    }
+    hunk
+}
+
+/// Format a hunk range (`start,len`, 1-based) from a line index. An empty range is
+/// identified by the line before it.
+fn range(index: usize, len: usize) -> String {
+    match len {
+        0 => format!("{},0", index),
+        1 => format!("{}", index + 1),
+        _ => format!("{},{}", index + 1, len),
+    }
+}
+
+/// Compute the edit script from `old` to `new` (longest common subsequence).
+fn diff_ops(old: &[&str], new: &[&str]) -> Vec<Op> {
+    // lcs[i][j] is the length of the LCS of old[i..] and new[j..]
+    let mut lcs = vec![vec![0u32; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = match old[i] == new[j] {
+                true => lcs[i + 1][j + 1] + 1,
+                false => lcs[i + 1][j].max(lcs[i][j + 1]),
+            };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    let mut ops = Vec::<Op>::with_capacity(old.len().max(new.len()));
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            ops.push(Op::Equal(i, j));
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            ops.push(Op::Delete(i, j));
+            i += 1;
+        } else {
+            ops.push(Op::Insert(i, j));
+            j += 1;
+        }
+    }
+    ops
+}
+
+//**********************************************************************************************
+// Unit tests
+//**********************************************************************************************
+
+#[cfg(test)]
+mod tests {
+    use super::{change_counts, unified_diff};
+
+    #[test]
+    fn identical_lines_have_no_diff() {
+        assert_eq!(unified_diff("f", &["a", "b"], &["a", "b"]), "");
+        assert_eq!(unified_diff("f", &[], &[]), "");
+    }
+
+    #[test]
+    fn changes_are_shown_with_context() {
+        let old = ["1", "2", "3", "4", "5", "6", "7", "8"];
+        let new = ["1", "2", "3", "4", "x", "6", "7", "8"];
+        assert_eq!(
+            unified_diff("f", &old, &new),
+            "--- f\n+++ f\n@@ -2,7 +2,7 @@\n 2\n 3\n 4\n-5\n+x\n 6\n 7\n 8\n"
+        );
+    }
+
+    #[test]
+    fn distant_changes_are_separate_hunks() {
+        let old: Vec<String> = (1..=20).map(|n| n.to_string()).collect();
+        let old: Vec<&str> = old.iter().map(String::as_str).collect();
+        let mut new = old.clone();
+        new.remove(1);
+        new.push("21");
+        assert_eq!(
+            unified_diff("f", &old, &new),
+            concat!(
+                "--- f\n+++ f\n",
+                "@@ -1,5 +1,4 @@\n 1\n-2\n 3\n 4\n 5\n",
+                "@@ -18,3 +17,4 @@\n 18\n 19\n 20\n+21\n"
+            )
+        );
+    }
+
+    #[test]
+    fn changes_are_counted() {
+        assert_eq!(change_counts(&["a", "b"], &["a", "b"]), (0, 0));
+        assert_eq!(change_counts(&["a", "b", "c"], &["a", "x", "c", "d"]), (2, 1));
+    }
+
+    #[test]
+    fn empty_files_are_handled() {
+        assert_eq!(unified_diff("f", &[], &["a"]), "--- f\n+++ f\n@@ -0,0 +1 @@\n+a\n");
+        assert_eq!(unified_diff("f", &["a"], &[]), "--- f\n+++ f\n@@ -1 +0,0 @@\n-a\n");
+    }
+}
+
+// end
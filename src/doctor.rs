@@ -0,0 +1,447 @@
+//! Environment diagnostics
+//!
+//! Check the environment the aliases depend on (`avahi-alias doctor`): Avahi, nss-mdns and
+//! its configuration, the aliases file, the daemon's service, and whether the aliases
+//! resolve to this host. Each check reports a `Diagnosis`: it passes, warns, or fails with a
+//! suggested fix.
+
+#![warn(clippy::all)]
+
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+use std::{fs, process};
+
+use anyhow::Result;
+
+use crate::avahi_dbus::{self, avahi, OrgFreedesktopAvahiServer};
+use crate::lookup::{alias_status, AliasStatus, LocalHost};
+use crate::output::Value;
+use crate::{template, AliasesFile};
+
+/// The name service switch configuration (which must use nss-mdns for `.local` names)
+const NSSWITCH_CONF: &str = "/etc/nsswitch.conf";
+
+/// The nss-mdns configuration for names with more than one label before `.local`
+const MDNS_ALLOW: &str = "/etc/mdns.allow";
+
+/// The systemd unit running `avahi-alias-daemon`
+const SERVICE_UNIT: &str = "avahi-aliases.service";
+
+/// The health reported by a check
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Health {
+    Pass,
+    Warn,
+    Fail,
+}
+
+impl Health {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Health::Pass => "pass",
+            Health::Warn => "warn",
+            Health::Fail => "fail",
+        }
+    }
+}
+
+/// The result of a check, with a suggested fix unless it passed
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnosis {
+    /// The name of the check (e.g., `nsswitch`)
+    pub check: &'static str,
+    pub health: Health,
+    pub message: String,
+    pub fix: Option<String>,
+}
+
+impl Diagnosis {
+    fn pass(check: &'static str, message: String) -> Self {
+        Self { check, health: Health::Pass, message, fix: None }
+    }
+
+    fn warn(check: &'static str, message: String, fix: &str) -> Self {
+        Self { check, health: Health::Warn, message, fix: Some(fix.to_owned()) }
+    }
+
+    fn fail(check: &'static str, message: String, fix: &str) -> Self {
+        Self { check, health: Health::Fail, message, fix: Some(fix.to_owned()) }
+    }
+
+    pub fn to_record(&self) -> Vec<Value> {
+        vec![
+            Value::Text(self.check.to_owned()),
+            Value::Text(self.health.name().to_owned()),
+            Value::Text(self.message.clone()),
+            Value::optional_text(self.fix.as_deref()),
+        ]
+    }
+}
+
+/// Run every check for the aliases file `filename`, using the D-Bus connection (if one
+/// could be opened).
+pub fn diagnose(
+    dbus_connection: Result<&dbus::blocking::Connection, &anyhow::Error>, filename: &str,
+) -> Vec<Diagnosis> {
+    let mut diagnoses = Vec::<Diagnosis>::new();
+    let avahi_server_proxy = match dbus_connection {
+        Ok(dbus_connection) => Some(dbus_connection.with_proxy(
+            avahi::AVAHI_DBUS_NAME,
+            avahi::AVAHI_DBUS_PATH_SERVER,
+            crate::DEFAULT_TIMEOUT,
+        )),
+        Err(error) => {
+            diagnoses.push(Diagnosis::fail(
+                "avahi-daemon",
+                format!("avahi-daemon is not reachable on the system bus: {:#}", error),
+                "Start Avahi: sudo systemctl enable --now avahi-daemon",
+            ));
+            None
+        },
+    };
+    let running =
+        avahi_server_proxy.as_ref().is_some_and(|proxy| diagnose_avahi(proxy, &mut diagnoses));
+    let avahi_server_proxy = avahi_server_proxy.filter(|_| running);
+    diagnoses.push(diagnose_nsswitch());
+    let names = published_names(avahi_server_proxy.as_ref(), filename);
+    if let Ok(names) = &names {
+        diagnoses.push(diagnose_mdns_allow(names, Path::new(MDNS_ALLOW).exists()));
+    }
+    diagnoses.push(diagnose_aliases_file(filename));
+    diagnoses.push(diagnose_service());
+    if let (Some(proxy), Ok(_)) = (&avahi_server_proxy, &names) {
+        diagnoses.push(diagnose_published(proxy, filename));
+    }
+    diagnoses
+}
+
+/// Check that Avahi is reachable and running, and supports nss-mdns. Returns `true` if the
+/// Avahi server is running.
+fn diagnose_avahi(
+    avahi_server_proxy: &avahi_dbus::DBusProxy<'_, '_>, diagnoses: &mut Vec<Diagnosis>,
+) -> bool {
+    let version = match avahi_server_proxy.get_version_string() {
+        Ok(version) => version,
+        Err(error) => {
+            diagnoses.push(Diagnosis::fail(
+                "avahi-daemon",
+                format!("avahi-daemon is not reachable on the system bus: {}", error),
+                "Start Avahi: sudo systemctl enable --now avahi-daemon",
+            ));
+            return false;
+        },
+    };
+    diagnoses.push(Diagnosis::pass("avahi-daemon", format!("{} is reachable", version)));
+    let running = match avahi_server_proxy.get_state() {
+        Ok(state) if state == avahi::ServerState::RUNNING as i32 => {
+            diagnoses.push(Diagnosis::pass("server-state", String::from("Avahi is running")));
+            true
+        },
+        Ok(state) => {
+            diagnoses.push(Diagnosis::fail(
+                "server-state",
+                format!("Avahi is not running (state {})", state),
+                "Check the Avahi log (journalctl -u avahi-daemon); a host name collision \
+                 requires a new host name",
+            ));
+            false
+        },
+        Err(error) => {
+            diagnoses.push(Diagnosis::fail(
+                "server-state",
+                format!("could not get the Avahi state: {}", error),
+                "Check the Avahi log (journalctl -u avahi-daemon)",
+            ));
+            false
+        },
+    };
+    diagnoses.push(match avahi_server_proxy.is_nsssupport_available() {
+        Ok(true) => Diagnosis::pass("nss-support", String::from("nss-mdns is available")),
+        Ok(false) => Diagnosis::warn(
+            "nss-support",
+            String::from("nss-mdns is not available, so programs cannot resolve .local names"),
+            "Install nss-mdns (e.g., sudo apt install libnss-mdns)",
+        ),
+        Err(error) => Diagnosis::warn(
+            "nss-support",
+            format!("could not check for nss-mdns: {}", error),
+            "Install nss-mdns (e.g., sudo apt install libnss-mdns)",
+        ),
+    });
+    running
+}
+
+/// Check that the `hosts` line of nsswitch.conf uses mDNS.
+fn diagnose_nsswitch() -> Diagnosis {
+    match fs::read_to_string(NSSWITCH_CONF) {
+        Ok(text) => nsswitch_diagnosis(&text),
+        Err(error) => Diagnosis::fail(
+            "nsswitch",
+            format!("could not read {}: {}", NSSWITCH_CONF, error),
+            &nsswitch_fix(),
+        ),
+    }
+}
+
+fn nsswitch_fix() -> String {
+    format!(
+        "Add mdns4_minimal [NOTFOUND=return] before dns to the hosts line of {}",
+        NSSWITCH_CONF
+    )
+}
+
+/// Check that the `hosts` line of nsswitch.conf (`text`) uses mDNS.
+fn nsswitch_diagnosis(text: &str) -> Diagnosis {
+    let hosts = text
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or_default().trim())
+        .find_map(|line| line.strip_prefix("hosts:"));
+    match hosts {
+        Some(hosts) if hosts.split_whitespace().any(|s| s.starts_with("mdns")) => {
+            Diagnosis::pass("nsswitch", format!("{} uses mDNS for hosts", NSSWITCH_CONF))
+        },
+        Some(_) => Diagnosis::fail(
+            "nsswitch",
+            format!("the hosts line of {} does not use mDNS", NSSWITCH_CONF),
+            &nsswitch_fix(),
+        ),
+        None => Diagnosis::fail(
+            "nsswitch",
+            format!("{} has no hosts line", NSSWITCH_CONF),
+            &nsswitch_fix(),
+        ),
+    }
+}
+
+/// The names this host publishes: the aliases for this host (with templates expanded) and
+/// the host name they point to. Without Avahi, the aliases (other than templates) in the
+/// file.
+fn published_names(
+    avahi_server_proxy: Option<&avahi_dbus::DBusProxy<'_, '_>>, filename: &str,
+) -> Result<Vec<String>> {
+    let aliases_file = AliasesFile::from_file(filename, true)?;
+    let mut names = Vec::<String>::new();
+    let host = avahi_server_proxy.and_then(|proxy| {
+        Some((proxy.get_host_name().ok()?, proxy.get_host_name_fqdn().ok()?))
+    });
+    match host {
+        Some((host_name, fqdn)) => {
+            let variables = template::host_variables(&host_name);
+            let aliases_file = aliases_file.for_host(&host_name).expand_defined(&variables);
+            names.extend(published_aliases(&aliases_file).map(str::to_owned));
+            names.push(fqdn);
+        },
+        None => names.extend(published_aliases(&aliases_file).map(str::to_owned)),
+    }
+    Ok(names)
+}
+
+/// The aliases to publish, less templates that are not expanded (e.g., using variables defined
+/// on the daemon command line).
+fn published_aliases(aliases_file: &AliasesFile) -> impl Iterator<Item = &str> {
+    aliases_file.aliases().into_iter().filter(|alias| !template::is_template(alias))
+}
+
+/// Returns `true` if nss-mdns only resolves `name` if mdns.allow allows it (i.e., it has
+/// more than one label before `.local`, or is not in `.local`).
+fn needs_mdns_allow(name: &str) -> bool {
+    let name = name.trim_end_matches('.').to_ascii_lowercase();
+    match name.strip_suffix(".local") {
+        Some(labels) => labels.contains('.'),
+        None => true,
+    }
+}
+
+/// Check for mdns.allow (`allow_exists`) if any published name needs it.
+fn diagnose_mdns_allow<T>(names: &[T], allow_exists: bool) -> Diagnosis
+where
+    T: AsRef<str>, {
+    let needy: Vec<&str> =
+        names.iter().map(AsRef::as_ref).filter(|name| needs_mdns_allow(name)).collect();
+    match (needy.is_empty(), allow_exists) {
+        (true, _) => Diagnosis::pass(
+            "mdns-allow",
+            String::from("no published names need mdns.allow (all are single-label .local)"),
+        ),
+        (false, true) => Diagnosis::pass("mdns-allow", format!("{} is present", MDNS_ALLOW)),
+        (false, false) => Diagnosis::warn(
+            "mdns-allow",
+            format!(
+                "{} needs {}, which is missing (nss-mdns only resolves single-label .local \
+                 names without it)",
+                needy.join(", "),
+                MDNS_ALLOW
+            ),
+            &format!(
+                "Create {} allowing .local (and use mdns4 rather than mdns4_minimal)",
+                MDNS_ALLOW
+            ),
+        ),
+    }
+}
+
+/// Check that the aliases file exists, is owned by root, and is not world-writable.
+fn diagnose_aliases_file(filename: &str) -> Diagnosis {
+    match fs::metadata(filename) {
+        Ok(metadata) => aliases_file_diagnosis(filename, metadata.uid(), metadata.mode()),
+        Err(error) => Diagnosis::fail(
+            "aliases-file",
+            format!("could not read {}: {}", filename, error),
+            &format!("Create the aliases file: sudo touch {}", filename),
+        ),
+    }
+}
+
+/// Check the owner (`uid`) and `mode` of the aliases file.
+fn aliases_file_diagnosis(filename: &str, uid: u32, mode: u32) -> Diagnosis {
+    let mode = mode & 0o7777;
+    match (uid, mode & 0o002 != 0) {
+        (_, true) => Diagnosis::fail(
+            "aliases-file",
+            format!("{} is world-writable (mode {:o})", filename, mode),
+            &format!("sudo chmod o-w {}", filename),
+        ),
+        (0, false) => Diagnosis::pass(
+            "aliases-file",
+            format!("{} is owned by root (mode {:o})", filename, mode),
+        ),
+        (uid, false) => Diagnosis::warn(
+            "aliases-file",
+            format!("{} is owned by user {}, not root (mode {:o})", filename, uid, mode),
+            &format!("sudo chown root: {}", filename),
+        ),
+    }
+}
+
+/// Check that the daemon's systemd unit is active.
+fn diagnose_service() -> Diagnosis {
+    let fix = format!("sudo systemctl enable --now {}", SERVICE_UNIT);
+    match process::Command::new("systemctl").args(["is-active", SERVICE_UNIT]).output() {
+        Ok(output) => match String::from_utf8_lossy(&output.stdout).trim() {
+            "active" => Diagnosis::pass("service", format!("{} is active", SERVICE_UNIT)),
+            "" => Diagnosis::warn(
+                "service",
+                format!("could not get the state of {} (is systemd running?)", SERVICE_UNIT),
+                "Run avahi-alias-daemon (e.g., as a systemd service)",
+            ),
+            state => Diagnosis::fail("service", format!("{} is {}", SERVICE_UNIT, state), &fix),
+        },
+        Err(error) => Diagnosis::warn(
+            "service",
+            format!("could not run systemctl: {}", error),
+            "Run avahi-alias-daemon (e.g., as a systemd service)",
+        ),
+    }
+}
+
+/// Check that each alias in the file for the local host resolves to the local host.
+fn diagnose_published(
+    avahi_server_proxy: &avahi_dbus::DBusProxy<'_, '_>, filename: &str,
+) -> Diagnosis {
+    published_diagnosis(avahi_server_proxy, filename).unwrap_or_else(|error| {
+        Diagnosis::fail(
+            "published",
+            format!("could not resolve the aliases: {:#}", error),
+            "Check the Avahi log (journalctl -u avahi-daemon)",
+        )
+    })
+}
+
+fn published_diagnosis(
+    avahi_server_proxy: &avahi_dbus::DBusProxy<'_, '_>, filename: &str,
+) -> Result<Diagnosis> {
+    let host_name = avahi_server_proxy.get_host_name()?;
+    let local_host = LocalHost::new(avahi_server_proxy)?;
+    let variables = template::host_variables(&host_name);
+    let aliases_file =
+        AliasesFile::from_file(filename, true)?.for_host(&host_name).expand_defined(&variables);
+    let statuses: Vec<AliasStatus> = published_aliases(&aliases_file)
+        .map(|alias| alias_status(avahi_server_proxy, &local_host, alias))
+        .collect();
+    Ok(unpublished_diagnosis(&statuses))
+}
+
+/// Report the aliases that do not resolve to this host.
+fn unpublished_diagnosis(statuses: &[AliasStatus]) -> Diagnosis {
+    let unpublished: Vec<String> = statuses
+        .iter()
+        .filter(|status| status.status != "published")
+        .map(|status| format!("{} ({})", status.alias, status.status))
+        .collect();
+    match unpublished.is_empty() {
+        true => Diagnosis::pass(
+            "published",
+            format!("all {} aliases in the file resolve to this host", statuses.len()),
+        ),
+        false => Diagnosis::fail(
+            "published",
+            format!("not published: {}", unpublished.join(", ")),
+            "Check the daemon log (journalctl -u avahi-aliases) and avahi-alias status",
+        ),
+    }
+}
+
+//**********************************************************************************************
+// Unit tests
+//**********************************************************************************************
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        aliases_file_diagnosis, diagnose_mdns_allow, needs_mdns_allow, nsswitch_diagnosis,
+        Health,
+    };
+
+    #[test]
+    fn nsswitch_hosts_must_use_mdns() {
+        let data = [
+            ("hosts: files mdns4_minimal [NOTFOUND=return] dns\n", Health::Pass),
+            ("# hosts: mdns\nhosts:\tfiles mdns dns # mDNS\n", Health::Pass),
+            ("passwd: files\nhosts: files dns # mdns4_minimal\n", Health::Fail),
+            ("# hosts: files mdns4_minimal dns\n", Health::Fail),
+            ("", Health::Fail),
+        ];
+        for (text, health) in data {
+            assert_eq!(nsswitch_diagnosis(text).health, health, "{:?}", text);
+        }
+        assert!(nsswitch_diagnosis("").message.contains("no hosts line"));
+    }
+
+    #[test]
+    fn aliases_files_should_be_owned_by_root_and_not_world_writable() {
+        let data = [
+            (0, 0o100644, Health::Pass, "owned by root (mode 644)"),
+            (0, 0o100666, Health::Fail, "world-writable (mode 666)"),
+            (1000, 0o100646, Health::Fail, "world-writable"),
+            (1000, 0o100644, Health::Warn, "owned by user 1000"),
+        ];
+        for (uid, mode, health, message) in data {
+            let diagnosis = aliases_file_diagnosis("/etc/avahi/avahi-aliases", uid, mode);
+            assert_eq!(diagnosis.health, health, "{} {:o}", uid, mode);
+            assert!(diagnosis.message.contains(message), "{}", diagnosis.message);
+            assert_eq!(diagnosis.fix.is_some(), health != Health::Pass);
+        }
+    }
+
+    #[test]
+    fn multi_label_and_non_local_names_need_mdns_allow() {
+        assert!(!needs_mdns_allow("a.local"));
+        assert!(!needs_mdns_allow("Gandalf.local."));
+        assert!(needs_mdns_allow("a.b.local"));
+        assert!(needs_mdns_allow("gandalf.example.com"));
+    }
+
+    #[test]
+    fn mdns_allow_is_needed_only_for_published_names_that_need_it() {
+        let single = ["a.local", "gandalf.local"];
+        assert_eq!(diagnose_mdns_allow(&single, false).health, Health::Pass);
+        let multi = ["a.local", "gandalf.lab.local"];
+        let diagnosis = diagnose_mdns_allow(&multi, false);
+        assert_eq!(diagnosis.health, Health::Warn);
+        assert!(diagnosis.message.starts_with("gandalf.lab.local needs"));
+        assert_eq!(diagnose_mdns_allow(&multi, true).health, Health::Pass);
+        assert_eq!(diagnose_mdns_allow::<&str>(&[], false).health, Health::Pass);
+    }
+}
+
+// end
@@ -2,6 +2,8 @@
 
 #![warn(clippy::all)]
 
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
 pub fn encode_rdata(name: &str) -> Vec<u8> {
     // TODO: fix capacity to account for IDNA
     let mut rdata: Vec<u8> = Vec::<u8>::with_capacity(name.len() + 1);
@@ -14,6 +16,32 @@ pub fn encode_rdata(name: &str) -> Vec<u8> {
     rdata
 }
 
+/// Decode name rdata (e.g., the target of a CNAME record). Returns `None` if the rdata is
+/// malformed. (mDNS rdata is uncompressed.)
+pub fn decode_rdata_name(rdata: &[u8]) -> Option<String> {
+    let mut labels = Vec::<String>::new();
+    let mut rest = rdata;
+    loop {
+        let (&len, tail) = rest.split_first()?;
+        if len == 0 {
+            return tail.is_empty().then(|| labels.join("."));
+        }
+        let label = tail.get(..len as usize)?;
+        labels.push(String::from_utf8_lossy(label).into_owned());
+        rest = &tail[len as usize..];
+    }
+}
+
+/// Decode address rdata (A or AAAA records). Returns `None` if the rdata is not an IPv4 or
+/// IPv6 address.
+pub fn decode_rdata_address(rdata: &[u8]) -> Option<IpAddr> {
+    match rdata.len() {
+        4 => Some(IpAddr::V4(Ipv4Addr::from(<[u8; 4]>::try_from(rdata).ok()?))),
+        16 => Some(IpAddr::V6(Ipv6Addr::from(<[u8; 16]>::try_from(rdata).ok()?))),
+        _ => None,
+    }
+}
+
 /// Convert IDNA domains to ASCII (currently a no-op/passthrough)
 pub fn to_ascii(idna_name: &str) -> String { idna_name.to_owned() }
 
@@ -47,6 +75,32 @@ mod tests {
         }
     }
 
+    #[test]
+    fn decode_rdata_name_decodes_correctly() {
+        for (name, encoded) in TEST_RDATA.iter().filter(|(n, _)| !n.contains("..")) {
+            assert_eq!(super::decode_rdata_name(encoded).as_deref(), Some(*name));
+        }
+    }
+
+    #[test]
+    fn decode_rdata_name_rejects_malformed_rdata() {
+        for rdata in [&[][..], &[1, b'a'], &[5, b'a', 0], &[1, b'a', 0, 0]] {
+            assert_eq!(super::decode_rdata_name(rdata), None);
+        }
+    }
+
+    #[test]
+    fn decode_rdata_address_decodes_correctly() {
+        let ipv4 = super::decode_rdata_address(&[192, 168, 1, 5]).unwrap();
+        assert_eq!(ipv4.to_string(), "192.168.1.5");
+        let mut rdata = [0u8; 16];
+        rdata[0] = 0xfe;
+        rdata[1] = 0x80;
+        rdata[15] = 1;
+        assert_eq!(super::decode_rdata_address(&rdata).unwrap().to_string(), "fe80::1");
+        assert_eq!(super::decode_rdata_address(&[1, 2, 3]), None);
+    }
+
     #[test]
     fn to_ascii_encodes_correctly() {
         for (name, encoded) in TEST_IDNA {
@@ -0,0 +1,149 @@
+//! Alias expiry
+//!
+//! Temporary aliases have an `expires` attribute holding an RFC 3339 timestamp in UTC (e.g.,
+//! `demo.local # expires=2026-10-18T16:00:00Z`). The daemon stops publishing an alias when it
+//! expires, and `avahi-alias prune` removes expired lines from the file.
+
+#![warn(clippy::all)]
+
+use std::num::IntErrorKind;
+
+use time::format_description::well_known::Rfc3339;
+use time::{Duration, OffsetDateTime, UtcOffset};
+
+/// The attribute holding the time an alias expires
+pub const EXPIRES_ATTRIBUTE: &str = "expires";
+
+/// Parse an expiry: a duration from `now` (e.g., `2h`, `1h30m`, or `7d`; the units are `s`,
+/// `m`, `h`, `d`, and `w`) or an RFC 3339 timestamp (e.g., `2026-10-18T16:00:00Z`). The expiry
+/// must be after `now`.
+///
+/// # Examples
+///
+/// ```
+/// use avahi_aliases::expiry::{format_expiry, parse_expiry, parse_timestamp};
+///
+/// let now = parse_timestamp("2026-10-18T14:00:00Z").unwrap();
+/// assert_eq!(format_expiry(parse_expiry("2h", now).unwrap()), "2026-10-18T16:00:00Z");
+/// ```
+pub fn parse_expiry(text: &str, now: OffsetDateTime) -> Result<OffsetDateTime, String> {
+    let out_of_range = || format!(r#"expiry "{}" is out of range"#, text);
+    let expiry = match parse_duration(text) {
+        Some(duration) => {
+            duration.and_then(|duration| now.checked_add(duration)).ok_or_else(out_of_range)?
+        },
+        None => parse_timestamp(text).ok_or_else(|| {
+            format!(r#"invalid expiry "{}" (expected a duration like 2h or a timestamp)"#, text)
+        })?,
+    };
+    match expiry > now {
+        true => Ok(expiry.replace_nanosecond(0).unwrap_or(expiry)),
+        false => Err(format!(r#"expiry "{}" is not in the future"#, text)),
+    }
+}
+
+/// Parse an `expires` attribute value (an RFC 3339 timestamp).
+pub fn parse_timestamp(text: &str) -> Option<OffsetDateTime> {
+    OffsetDateTime::parse(text, &Rfc3339).ok()
+}
+
+/// Format an expiry as an `expires` attribute value (an RFC 3339 timestamp in UTC).
+pub fn format_expiry(expiry: OffsetDateTime) -> String {
+    expiry.to_offset(UtcOffset::UTC).format(&Rfc3339).unwrap_or_default()
+}
+
+/// Parse a duration made of numbers followed by units (e.g., `1h30m`). Returns `Some(None)`
+/// if the duration is too long to represent.
+fn parse_duration(text: &str) -> Option<Option<Duration>> {
+    let mut seconds: Option<i64> = Some(0);
+    let mut rest = text;
+    while !rest.is_empty() {
+        let digits = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+        let number: Option<i64> = match rest[..digits].parse::<i64>() {
+            Ok(number) => Some(number),
+            Err(error) if *error.kind() == IntErrorKind::PosOverflow => None,
+            Err(_) => return None,
+        };
+        let unit = rest[digits..].chars().next()?;
+        let unit_seconds = match unit {
+            's' => 1,
+            'm' => 60,
+            'h' => 3_600,
+            'd' => 86_400,
+            'w' => 604_800,
+            _ => return None,
+        };
+        seconds = seconds.zip(number).and_then(|(seconds, number)| {
+            seconds.checked_add(number.checked_mul(unit_seconds)?)
+        });
+        rest = &rest[digits + 1..];
+    }
+    (!text.is_empty()).then_some(seconds.map(Duration::seconds))
+}
+
+//**********************************************************************************************
+// Unit tests
+//**********************************************************************************************
+
+#[cfg(test)]
+mod tests {
+    use time::OffsetDateTime;
+
+    use super::{format_expiry, parse_expiry, parse_timestamp};
+
+    fn at(timestamp: &str) -> OffsetDateTime { parse_timestamp(timestamp).unwrap() }
+
+    #[test]
+    fn durations_are_added_to_now() {
+        let now = at("2026-10-18T14:00:00.5Z");
+        let data = [
+            ("45s", at("2026-10-18T14:00:45Z")),
+            ("2h", at("2026-10-18T16:00:00Z")),
+            ("1h30m", at("2026-10-18T15:30:00Z")),
+            ("1d", at("2026-10-19T14:00:00Z")),
+            ("1w", at("2026-10-25T14:00:00Z")),
+        ];
+        for (text, expiry) in data {
+            assert_eq!(parse_expiry(text, now), Ok(expiry));
+        }
+    }
+
+    #[test]
+    fn timestamps_are_accepted() {
+        let now = at("2026-10-18T14:00:00Z");
+        assert_eq!(
+            parse_expiry("2026-10-18T18:00:00+02:00", now),
+            Ok(at("2026-10-18T16:00:00Z"))
+        );
+        assert_eq!(parse_timestamp("2026-10-18T16:00:00Z"), Some(at("2026-10-18T16:00:00Z")));
+        assert_eq!(parse_timestamp("tomorrow"), None);
+    }
+
+    #[test]
+    fn invalid_and_past_expiries_are_rejected() {
+        let now = at("2026-10-18T14:00:00Z");
+        for text in ["", "2", "h", "2x", "-2h", "0h", "2026-10-18T13:00:00Z", "soon"] {
+            assert!(parse_expiry(text, now).is_err(), "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn out_of_range_expiries_are_rejected() {
+        let now = at("2026-10-18T14:00:00Z");
+        for text in ["100000000d", "99999999999999w", "1h99999999999999999999s"] {
+            assert_eq!(
+                parse_expiry(text, now),
+                Err(format!(r#"expiry "{}" is out of range"#, text)),
+                "{:?}",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn expiries_are_formatted_in_utc() {
+        assert_eq!(format_expiry(at("2026-10-18T18:00:00+02:00")), "2026-10-18T16:00:00Z");
+    }
+}
+
+// end
@@ -0,0 +1,266 @@
+//! Canonical formatting of aliases files
+//!
+//! The canonical format:
+//!
+//! - Aliases are lowercase, unindented, and followed by their host selectors (if any), each
+//!   separated by one space.
+//! - Inline comments in a block of consecutive alias lines are aligned.
+//! - Repeated blank lines are collapsed; leading and trailing blank lines are removed.
+//! - Exact duplicates (same alias, host selectors, state, and comment in the same group) are
+//!   removed; the first occurrence is kept. Other repeated aliases are kept (`check` warns
+//!   about them).
+//! - Comment lines and invalid aliases are preserved (less trailing whitespace).
+//!
+//! Optionally, the aliases within each block are sorted.
+
+#![warn(clippy::all)]
+
+use std::collections::HashSet;
+
+use crate::alias::new_alias_or_template;
+use crate::line::{DISABLED_MARKER, SELECTOR_MARKER};
+use crate::Line;
+
+/// A line, parsed for formatting
+enum Item<'a> {
+    Blank,
+    Verbatim(&'a str),
+    Header { content: String, comment: Option<&'a str> },
+    Alias { content: String, key: String, comment: Option<&'a str> },
+}
+
+/// Format lines canonically. If `sort` is `true`, the aliases in each block of consecutive
+/// alias lines are sorted.
+///
+/// Note: the lines must not be expanded (see `Line::expand`).
+pub fn format_lines(lines: &[Line], sort: bool) -> Vec<Line> {
+    // Parse, dropping duplicates and redundant blank lines
+    let mut items = Vec::<Item<'_>>::with_capacity(lines.len());
+    let mut seen = HashSet::<(Option<&str>, String, Option<&str>)>::new();
+    let mut group = None;
+    for line in lines {
+        let item = parse(line);
+        match &item {
+            Item::Blank if matches!(items.last(), None | Some(Item::Blank)) => continue,
+            Item::Header { .. } => group = line.group(),
+            Item::Alias { content, comment, .. }
+                if !seen.insert((group, content.clone(), comment.map(str::trim))) =>
+            {
+                continue
+            },
+            _ => (),
+        }
+        items.push(item);
+    }
+    if matches!(items.last(), Some(Item::Blank)) {
+        items.pop();
+    }
+    // Sort and align blocks of aliases
+    let mut formatted = Vec::<Line>::with_capacity(items.len());
+    let mut start = 0;
+    while start < items.len() {
+        let end = items[start..]
+            .iter()
+            .position(|item| !matches!(item, Item::Alias { .. }))
+            .map_or(items.len(), |i| start + i);
+        if start == end {
+            formatted.push(Line::new(render(&items[start], 0)));
+            start += 1;
+            continue;
+        }
+        let block = &mut items[start..end];
+        if sort {
+            block.sort_by(|a, b| sort_key(a).cmp(sort_key(b)));
+        }
+        let width = block.iter().map(content_width).max().unwrap_or_default();
+        formatted.extend(block.iter().map(|item| Line::new(render(item, width))));
+        start = end;
+    }
+    formatted
+}
+
+fn parse(line: &Line) -> Item<'_> {
+    let comment = line.comment().map(str::trim_end).filter(|c| !c.trim().is_empty());
+    let disabled = match line.is_disabled() {
+        true => DISABLED_MARKER.to_string(),
+        false => String::new(),
+    };
+    let selectors: String =
+        line.selectors().iter().map(|s| format!(" {}{}", SELECTOR_MARKER, s)).collect();
+    if let Some(group) = line.group() {
+        return Item::Header {
+            content: format!("{}[{}]{}", disabled, group, selectors),
+            comment,
+        };
+    }
+    match line.alias().map(|alias| lowercase_alias(alias.unwrap_or_else(|a| a))) {
+        Some(alias) if new_alias_or_template(&alias).is_ok() => Item::Alias {
+            content: format!("{}{}{}", disabled, alias, selectors),
+            key: alias,
+            comment,
+        },
+        None if line.text().trim().is_empty() => Item::Blank,
+        _ => Item::Verbatim(line.text().trim_end()),
+    }
+}
+
+fn render(item: &Item<'_>, width: usize) -> String {
+    match item {
+        Item::Blank => String::new(),
+        Item::Verbatim(text) => text.to_string(),
+        Item::Header { content, comment: None }
+        | Item::Alias { content, comment: None, .. } => content.clone(),
+        Item::Header { content, comment: Some(comment) } => format!("{} #{}", content, comment),
+        Item::Alias { content, comment: Some(comment), .. } => {
+            format!("{:width$} #{}", content, comment, width = width)
+        },
+    }
+}
+
+/// The width of an alias line's content (for aligning comments)
+fn content_width(item: &Item<'_>) -> usize {
+    match item {
+        Item::Alias { content, comment: Some(_), .. } => content.chars().count(),
+        _ => 0,
+    }
+}
+
+fn sort_key<'i>(item: &'i Item<'_>) -> &'i str {
+    match item {
+        Item::Alias { key, .. } => key,
+        _ => "",
+    }
+}
+
+/// Lowercase an alias, except for template placeholders (e.g., `{SITE}`).
+fn lowercase_alias(alias: &str) -> String {
+    let mut in_placeholder = false;
+    alias
+        .chars()
+        .map(|c| {
+            match c {
+                '{' => in_placeholder = true,
+                '}' => in_placeholder = false,
+                _ => (),
+            }
+            match in_placeholder {
+                true => c,
+                false => c.to_ascii_lowercase(),
+            }
+        })
+        .collect()
+}
+
+//**********************************************************************************************
+// Unit tests
+//**********************************************************************************************
+
+#[cfg(test)]
+mod tests {
+    use super::format_lines;
+    use crate::Line;
+
+    fn format(text: &[&str], sort: bool) -> Vec<String> {
+        let lines: Vec<Line> = text.iter().map(|t| Line::new(t.to_string())).collect();
+        format_lines(&lines, sort).iter().map(|line| line.text().to_owned()).collect()
+    }
+
+    #[test]
+    fn aliases_are_lowercased_and_trimmed() {
+        let text = ["  A.local  ", "\tB.Local", "!C.LOCAL @Web-*", "{SITE}-Db.local"];
+        assert_eq!(format(&text, false), vec![
+            "a.local",
+            "b.local",
+            "!c.local @Web-*",
+            "{SITE}-db.local"
+        ]);
+    }
+
+    #[test]
+    fn inline_comments_are_aligned_within_blocks() {
+        let text = [
+            "a.local # One",
+            "media-center.local\t#\tTwo  ",
+            "b.local",
+            "# Block comment",
+            "c.local  #Three",
+            "[web]    # Header",
+        ];
+        assert_eq!(format(&text, false), vec![
+            "a.local            # One",
+            "media-center.local #\tTwo",
+            "b.local",
+            "# Block comment",
+            "c.local #Three",
+            "[web] # Header",
+        ]);
+    }
+
+    #[test]
+    fn blank_lines_are_collapsed() {
+        let text = ["", "# Header", "", "", "a.local", " \t", "", "b.local", "", ""];
+        assert_eq!(format(&text, false), vec!["# Header", "", "a.local", "", "b.local"]);
+    }
+
+    #[test]
+    fn duplicates_are_removed_within_groups() {
+        let text = [
+            "a.local # A",
+            "A.local  #  A ",
+            "a.local # expires=2026-10-19T00:00:00Z",
+            "a.local # permanent",
+            "!a.local # A",
+            "[web]",
+            "a.local",
+            "a.local @x",
+        ];
+        assert_eq!(format(&text, false), vec![
+            "a.local  # A",
+            "a.local  # expires=2026-10-19T00:00:00Z",
+            "a.local  # permanent",
+            "!a.local # A",
+            "[web]",
+            "a.local",
+            "a.local @x"
+        ]);
+    }
+
+    #[test]
+    fn sort_sorts_within_blocks() {
+        let text =
+            ["# Header", "c.local", "!a.local # A", "b.local", "# Next", "z.local", "y.local"];
+        assert_eq!(format(&text, true), vec![
+            "# Header",
+            "!a.local # A",
+            "b.local",
+            "c.local",
+            "# Next",
+            "y.local",
+            "z.local",
+        ]);
+        assert_eq!(format(&text[..4], false), text[..4]);
+    }
+
+    #[test]
+    fn comments_and_invalid_aliases_are_preserved() {
+        let text =
+            ["##", "# Leading   comment ", "#", "  # indented", "x .local # bad  ", "a*.local"];
+        assert_eq!(format(&text, true), vec![
+            "##",
+            "# Leading   comment",
+            "#",
+            "  # indented",
+            "x .local # bad",
+            "a*.local"
+        ]);
+    }
+
+    #[test]
+    fn formatted_lines_are_unchanged() {
+        let text =
+            ["# Header", "", "a.local  # A", "bb.local # B", "", "[web] @web-*", "c.local"];
+        assert_eq!(format(&text, true), text);
+    }
+}
+
+// end
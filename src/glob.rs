@@ -0,0 +1,80 @@
+//! Shell-style glob patterns
+//!
+//! Match text against simple glob patterns, where `*` matches any sequence of characters
+//! and `?` matches exactly one character. Matching is case-insensitive because host names
+//! and aliases are case-insensitive.
+
+#![warn(clippy::all)]
+
+use regex::{Regex, RegexBuilder};
+
+/// Convert a glob pattern to an (anchored, case-insensitive) regular expression.
+///
+/// # Examples
+///
+/// ```
+/// use avahi_aliases::glob::glob_to_regex;
+///
+/// let re = glob_to_regex("monitor-*");
+/// assert!(re.is_match("monitor-01"));
+/// assert!(!re.is_match("web-01"));
+/// ```
+pub fn glob_to_regex(pattern: &str) -> Regex {
+    let mut re = String::with_capacity(pattern.len() + 8);
+    re.push('^');
+    for c in pattern.chars() {
+        match c {
+            '*' => re.push_str(".*"),
+            '?' => re.push('.'),
+            c => re.push_str(&regex::escape(c.encode_utf8(&mut [0u8; 4]))),
+        }
+    }
+    re.push('$');
+    RegexBuilder::new(&re).case_insensitive(true).build().unwrap()
+}
+
+/// Returns `true` if `text` matches the glob `pattern`.
+pub fn glob_matches(pattern: &str, text: &str) -> bool { glob_to_regex(pattern).is_match(text) }
+
+//**********************************************************************************************
+// Unit tests
+//**********************************************************************************************
+
+#[cfg(test)]
+mod tests {
+    use super::glob_matches;
+
+    #[test]
+    fn literal_patterns_match_exactly() {
+        assert!(glob_matches("monitor-01", "monitor-01"));
+        assert!(glob_matches("monitor-01", "Monitor-01"));
+        assert!(!glob_matches("monitor-01", "monitor-011"));
+        assert!(!glob_matches("monitor-01", "xmonitor-01"));
+    }
+
+    #[test]
+    fn star_matches_any_sequence() {
+        for text in ["monitor-", "monitor-01", "monitor-east-02"] {
+            assert!(glob_matches("monitor-*", text));
+        }
+        assert!(glob_matches("pr-*.local", "pr-1234.local"));
+        assert!(!glob_matches("monitor-*", "web-01"));
+    }
+
+    #[test]
+    fn question_mark_matches_one_character() {
+        assert!(glob_matches("web-0?", "web-01"));
+        assert!(!glob_matches("web-0?", "web-0"));
+        assert!(!glob_matches("web-0?", "web-011"));
+    }
+
+    #[test]
+    fn regex_characters_are_literal() {
+        assert!(glob_matches("a.local", "a.local"));
+        assert!(!glob_matches("a.local", "abloca"));
+        assert!(!glob_matches("a.local", "axlocal"));
+        assert!(glob_matches("(a)+", "(a)+"));
+    }
+}
+
+// end
@@ -0,0 +1,287 @@
+//! Aliases file history
+//!
+//! Before an aliases file is changed, its previous content is saved as a version in a history
+//! directory (by default `/var/lib/avahi-aliases/history`, if it exists), with who changed it
+//! and how. Only the most recent versions are kept. `avahi-alias history` lists the versions,
+//! and `avahi-alias undo` restores one.
+
+#![warn(clippy::all)]
+
+use std::path::{Path, PathBuf};
+use std::{env, fs, io};
+
+use anyhow::{Context, Result};
+use time::format_description::well_known::Rfc3339;
+use time::{Duration, OffsetDateTime, UtcOffset};
+
+/// The default history directory
+pub const HISTORY_DIR: &str = "/var/lib/avahi-aliases/history";
+
+/// The default number of versions kept for each aliases file (the `--history-limit` default)
+pub const HISTORY_LIMIT: &str = "20";
+
+/// The suffix of the files describing versions
+const INFO_SUFFIX: &str = ".info";
+
+/// The saved versions of aliases files
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct History {
+    dir: PathBuf,
+    limit: usize,
+    user: String,
+    command: String,
+}
+
+/// A saved version of an aliases file
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Version {
+    /// The file holding the saved content
+    pub path: PathBuf,
+    /// When the version was replaced
+    pub time: OffsetDateTime,
+    /// Who replaced it
+    pub user: String,
+    /// The command that replaced it
+    pub command: String,
+}
+
+impl Version {
+    /// The time the version was replaced as an RFC 3339 timestamp (in UTC, to the second)
+    pub fn timestamp(&self) -> String {
+        let time = self.time.replace_nanosecond(0).unwrap_or(self.time);
+        time.format(&Rfc3339).unwrap_or_default()
+    }
+}
+
+impl History {
+    /// Keep up to `limit` versions of each aliases file in `dir` (no versions if `limit` is
+    /// 0). Changes are attributed to the current user and command.
+    pub fn new(dir: impl Into<PathBuf>, limit: usize) -> Self {
+        Self { dir: dir.into(), limit, user: current_user(), command: current_command() }
+    }
+
+    /// Save `text`, the content of `file_name` before a change, and remove the versions
+    /// beyond the limit.
+    pub fn record(&self, file_name: &str, text: &str) -> Result<()> {
+        if self.limit == 0 {
+            return Ok(());
+        }
+        let dir = self.file_dir(file_name);
+        fs::create_dir_all(&dir)
+            .with_context(|| format!(r#"could not create "{}""#, dir.display()))?;
+        let mut time = OffsetDateTime::now_utc();
+        while dir.join(version_name(time)).exists() {
+            time += Duration::microseconds(1);
+        }
+        let path = dir.join(version_name(time));
+        fs::write(&path, text)
+            .and_then(|_| {
+                let info = format!("user={}\ncommand={}\n", self.user, self.command);
+                fs::write(info_path(&path), info)
+            })
+            .with_context(|| format!(r#"could not write "{}""#, path.display()))?;
+        for version in self.versions(file_name)?.iter().skip(self.limit) {
+            let _ = fs::remove_file(info_path(&version.path));
+            fs::remove_file(&version.path)
+                .with_context(|| format!(r#"could not remove "{}""#, version.path.display()))?;
+        }
+        Ok(())
+    }
+
+    /// Return the saved versions of `file_name`, newest first.
+    pub fn versions(&self, file_name: &str) -> Result<Vec<Version>> {
+        let dir = self.file_dir(file_name);
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(error) => {
+                return Err(error)
+                    .with_context(|| format!(r#"could not read "{}""#, dir.display()))
+            },
+        };
+        let mut versions = Vec::<Version>::new();
+        for entry in entries {
+            let path = entry?.path();
+            let time =
+                match path.file_name().and_then(|name| parse_version_name(name.to_str()?)) {
+                    Some(time) => time,
+                    None => continue,
+                };
+            let info = fs::read_to_string(info_path(&path)).unwrap_or_default();
+            let field = |key: &str| {
+                let value =
+                    info.lines().find_map(|line| line.strip_prefix(key)?.strip_prefix('='));
+                value.unwrap_or_default().to_owned()
+            };
+            versions.push(Version {
+                time,
+                user: field("user"),
+                command: field("command"),
+                path,
+            });
+        }
+        versions.sort_by_key(|version| std::cmp::Reverse(version.time));
+        Ok(versions)
+    }
+
+    /// The directory holding the versions of `file_name`, named after its absolute path
+    fn file_dir(&self, file_name: &str) -> PathBuf {
+        let path = fs::canonicalize(file_name).unwrap_or_else(|_| {
+            env::current_dir()
+                .map(|dir| dir.join(file_name))
+                .unwrap_or_else(|_| file_name.into())
+        });
+        let name = path.to_string_lossy().replace('%', "%25").replace('/', "%2F");
+        self.dir.join(name)
+    }
+}
+
+/// The file describing a version (who replaced it and how)
+fn info_path(path: &Path) -> PathBuf {
+    let mut info_path = path.as_os_str().to_owned();
+    info_path.push(INFO_SUFFIX);
+    info_path.into()
+}
+
+/// The name of a version replaced at `time`: the time in UTC, to the microsecond (e.g.,
+/// `20261018T140000.500000Z`), so the names sort by time.
+fn version_name(time: OffsetDateTime) -> String {
+    let time = time.to_offset(UtcOffset::UTC);
+    format!(
+        "{:04}{:02}{:02}T{:02}{:02}{:02}.{:06}Z",
+        time.year(),
+        time.month() as u8,
+        time.day(),
+        time.hour(),
+        time.minute(),
+        time.second(),
+        time.microsecond()
+    )
+}
+
+fn parse_version_name(name: &str) -> Option<OffsetDateTime> {
+    let digits = |range: std::ops::Range<usize>| {
+        name.get(range).filter(|digits| digits.bytes().all(|b| b.is_ascii_digit()))
+    };
+    let (date, time, fraction) = (digits(0..8)?, digits(9..15)?, digits(16..22)?);
+    if name.len() != 23 || &name[8..9] != "T" || &name[15..16] != "." || !name.ends_with('Z') {
+        return None;
+    }
+    let timestamp = format!(
+        "{}-{}-{}T{}:{}:{}.{}Z",
+        &date[..4],
+        &date[4..6],
+        &date[6..],
+        &time[..2],
+        &time[2..4],
+        &time[4..],
+        fraction
+    );
+    OffsetDateTime::parse(&timestamp, &Rfc3339).ok()
+}
+
+/// The user making a change (the user who ran `sudo`, if any)
+fn current_user() -> String {
+    ["SUDO_USER", "USER", "LOGNAME"]
+        .iter()
+        .find_map(|name| env::var(name).ok().filter(|user| !user.is_empty()))
+        .unwrap_or_else(|| {
+            // SAFETY: getuid has no preconditions and cannot fail.
+            unsafe { libc::getuid() }.to_string()
+        })
+}
+
+/// The command making a change (e.g., `avahi-alias remove a.local`)
+fn current_command() -> String {
+    let mut args = env::args();
+    let program = args.next().unwrap_or_default();
+    let program = Path::new(&program).file_name().unwrap_or_default().to_string_lossy();
+    let args = args.map(|arg| match arg.contains(char::is_whitespace) || arg.is_empty() {
+        true => format!("{:?}", arg),
+        false => arg,
+    });
+    std::iter::once(program.into_owned()).chain(args).collect::<Vec<_>>().join(" ")
+}
+
+//**********************************************************************************************
+// Unit tests
+//**********************************************************************************************
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+
+    use super::{parse_version_name, version_name, History};
+    use crate::expiry::parse_timestamp;
+
+    /// A temporary history directory and aliases file (removed when dropped)
+    struct TestHistory {
+        dir: std::path::PathBuf,
+        file_name: String,
+    }
+
+    impl TestHistory {
+        fn new(name: &str) -> Self {
+            let dir = env::temp_dir().join(format!("{}-{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            let file_name = dir.join("avahi-aliases").to_string_lossy().into_owned();
+            fs::write(&file_name, "").unwrap();
+            Self { dir: dir.join("history"), file_name }
+        }
+    }
+
+    impl Drop for TestHistory {
+        fn drop(&mut self) { let _ = fs::remove_dir_all(self.dir.parent().unwrap()); }
+    }
+
+    #[test]
+    fn version_names_sort_by_time_and_parse() {
+        let time = parse_timestamp("2026-10-18T14:00:00.5Z").unwrap();
+        assert_eq!(version_name(time), "20261018T140000.500000Z");
+        assert_eq!(parse_version_name(&version_name(time)), Some(time));
+        assert_eq!(parse_version_name("20261018T140000.500000Z.info"), None);
+    }
+
+    #[test]
+    fn versions_are_recorded_newest_first() {
+        let test = TestHistory::new(stringify!(versions_are_recorded_newest_first));
+        let history = History::new(&test.dir, 5);
+        assert!(history.versions(&test.file_name).unwrap().is_empty());
+        history.record(&test.file_name, "a.local\n").unwrap();
+        history.record(&test.file_name, "b.local\n").unwrap();
+        let versions = history.versions(&test.file_name).unwrap();
+        assert_eq!(versions.len(), 2);
+        assert_eq!(fs::read_to_string(&versions[0].path).unwrap(), "b.local\n");
+        assert_eq!(fs::read_to_string(&versions[1].path).unwrap(), "a.local\n");
+        assert!(versions[0].time >= versions[1].time);
+        assert!(!versions[0].user.is_empty());
+        assert!(!versions[0].command.is_empty());
+        assert!(versions[0].timestamp().ends_with('Z'));
+    }
+
+    #[test]
+    fn versions_beyond_the_limit_are_removed() {
+        let test = TestHistory::new(stringify!(versions_beyond_the_limit_are_removed));
+        let history = History::new(&test.dir, 2);
+        for text in ["a.local\n", "b.local\n", "c.local\n"] {
+            history.record(&test.file_name, text).unwrap();
+        }
+        let versions = history.versions(&test.file_name).unwrap();
+        let texts: Vec<String> =
+            versions.iter().map(|version| fs::read_to_string(&version.path).unwrap()).collect();
+        assert_eq!(texts, vec!["c.local\n", "b.local\n"]);
+        let files = fs::read_dir(versions[0].path.parent().unwrap()).unwrap().count();
+        assert_eq!(files, 4);
+    }
+
+    #[test]
+    fn nothing_is_recorded_without_a_limit() {
+        let test = TestHistory::new(stringify!(nothing_is_recorded_without_a_limit));
+        let history = History::new(&test.dir, 0);
+        history.record(&test.file_name, "a.local\n").unwrap();
+        assert!(!test.dir.exists());
+    }
+}
+
+// end
@@ -0,0 +1,246 @@
+//! Alias interchange formats
+//!
+//! Export aliases to, and import `.local` names from, other formats: hosts files
+//! (`/etc/hosts`), Avahi hosts files (`/etc/avahi/hosts`), dnsmasq configuration, BIND zone
+//! files, and plain alias lists (as used by the Python avahi-aliases implementations).
+
+#![warn(clippy::all)]
+
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+/// An interchange format
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExchangeFormat {
+    /// Hosts file lines (`ADDRESS NAME`)
+    Hosts,
+    /// Avahi hosts file lines (`ADDRESS NAME`; one name per line)
+    AvahiHosts,
+    /// dnsmasq `host-record` options
+    Dnsmasq,
+    /// BIND zone file records (CNAME records for the aliases, A/AAAA records for the target)
+    BindZone,
+    /// One alias per line (e.g., the Python implementations' `/etc/avahi/aliases`)
+    Aliases,
+}
+
+impl FromStr for ExchangeFormat {
+    type Err = String;
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format {
+            "hosts" => Ok(ExchangeFormat::Hosts),
+            "avahi-hosts" => Ok(ExchangeFormat::AvahiHosts),
+            "dnsmasq" => Ok(ExchangeFormat::Dnsmasq),
+            "bind-zone" => Ok(ExchangeFormat::BindZone),
+            "aliases" => Ok(ExchangeFormat::Aliases),
+            _ => Err(format!(r#"invalid format "{}""#, format)),
+        }
+    }
+}
+
+impl fmt::Display for ExchangeFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ExchangeFormat::Hosts => "hosts",
+            ExchangeFormat::AvahiHosts => "avahi-hosts",
+            ExchangeFormat::Dnsmasq => "dnsmasq",
+            ExchangeFormat::BindZone => "bind-zone",
+            ExchangeFormat::Aliases => "aliases",
+        })
+    }
+}
+
+/// The TTL of exported DNS records (the TTL the daemon publishes aliases with)
+const EXPORT_TTL: u32 = 60;
+
+/// Render aliases of the host `target` (an FQDN, e.g., `host.local`) with its `addresses`.
+///
+/// # Examples
+///
+/// ```
+/// use avahi_aliases::interchange::{export_aliases, ExchangeFormat};
+///
+/// let addresses = ["192.168.1.2".parse().unwrap()];
+/// let hosts = export_aliases(ExchangeFormat::Hosts, &["a.local"], "host.local", &addresses);
+/// assert_eq!(hosts, "192.168.1.2\ta.local\n");
+/// ```
+pub fn export_aliases(
+    format: ExchangeFormat, aliases: &[&str], target: &str, addresses: &[IpAddr],
+) -> String {
+    let mut text = String::new();
+    match format {
+        ExchangeFormat::Hosts | ExchangeFormat::AvahiHosts => {
+            for address in addresses {
+                for alias in aliases {
+                    text.push_str(&format!("{}\t{}\n", address, alias));
+                }
+            }
+        },
+        ExchangeFormat::Dnsmasq => {
+            let addresses: Vec<String> = addresses.iter().map(IpAddr::to_string).collect();
+            for alias in aliases {
+                text.push_str(&format!("host-record={},{}\n", alias, addresses.join(",")));
+            }
+        },
+        ExchangeFormat::BindZone => {
+            let target = format!("{}.", target.trim_end_matches('.'));
+            for address in addresses {
+                let record_type = match address {
+                    IpAddr::V4(_) => "A",
+                    IpAddr::V6(_) => "AAAA",
+                };
+                text.push_str(&format!(
+                    "{}\t{}\tIN\t{}\t{}\n",
+                    target, EXPORT_TTL, record_type, address
+                ));
+            }
+            for alias in aliases {
+                text.push_str(&format!("{}.\t{}\tIN\tCNAME\t{}\n", alias, EXPORT_TTL, target));
+            }
+        },
+        ExchangeFormat::Aliases => {
+            aliases.iter().for_each(|a| text.push_str(&format!("{}\n", a)))
+        },
+    }
+    text
+}
+
+/// Return the `.local` names (lowercase, without duplicates) in `text`. Names are taken from
+/// hosts file entries, dnsmasq `address`, `cname`, and `host-record` options, BIND zone CNAME
+/// records, or alias lists, depending on the format.
+pub fn import_names(format: ExchangeFormat, text: &str) -> Vec<String> {
+    let mut names = Vec::<String>::new();
+    let lines = text.lines().map(|line| match format {
+        ExchangeFormat::BindZone => line.split(';').next().unwrap_or_default().trim(),
+        _ => line.split('#').next().unwrap_or_default().trim(),
+    });
+    for line in lines.filter(|line| !line.is_empty()) {
+        let candidates: Vec<&str> = match format {
+            ExchangeFormat::Hosts | ExchangeFormat::AvahiHosts => {
+                line.split_whitespace().skip(1).collect()
+            },
+            ExchangeFormat::Dnsmasq => dnsmasq_names(line),
+            ExchangeFormat::BindZone => zone_cname_owner(line).into_iter().collect(),
+            ExchangeFormat::Aliases => vec![line],
+        };
+        for name in candidates {
+            let name = name.trim_end_matches('.').to_ascii_lowercase();
+            if name.ends_with(".local") && !names.contains(&name) {
+                names.push(name);
+            }
+        }
+    }
+    names
+}
+
+/// Return the names defined by a dnsmasq option (`address=/NAME/...`, `cname=NAME,...`,
+/// or `host-record=NAME,...`).
+fn dnsmasq_names(line: &str) -> Vec<&str> {
+    let (option, value) = line.split_once('=').unwrap_or((line, ""));
+    match option.trim() {
+        "address" => {
+            let domains = value.trim().trim_start_matches('/').rsplit_once('/');
+            domains.map_or(vec![], |(domains, _)| domains.split('/').collect())
+        },
+        "cname" => {
+            value.rsplit_once(',').map_or(vec![], |(names, _)| names.split(',').collect())
+        },
+        "host-record" => {
+            value.split(',').take_while(|v| v.parse::<IpAddr>().is_err()).collect()
+        },
+        _ => vec![],
+    }
+}
+
+/// Return the owner name of a BIND zone CNAME record (e.g., `a.local. IN CNAME h.local.`).
+fn zone_cname_owner(line: &str) -> Option<&str> {
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    let cname = fields.iter().position(|field| field.eq_ignore_ascii_case("CNAME"))?;
+    (cname > 0).then(|| fields[0])
+}
+
+//**********************************************************************************************
+// Unit tests
+//**********************************************************************************************
+
+#[cfg(test)]
+mod tests {
+    use std::net::IpAddr;
+
+    use super::{export_aliases, import_names, ExchangeFormat};
+
+    const FORMATS: [(&str, ExchangeFormat); 5] = [
+        ("hosts", ExchangeFormat::Hosts),
+        ("avahi-hosts", ExchangeFormat::AvahiHosts),
+        ("dnsmasq", ExchangeFormat::Dnsmasq),
+        ("bind-zone", ExchangeFormat::BindZone),
+        ("aliases", ExchangeFormat::Aliases),
+    ];
+
+    fn addresses() -> Vec<IpAddr> {
+        vec!["192.168.1.2".parse().unwrap(), "fe80::1".parse().unwrap()]
+    }
+
+    #[test]
+    fn exchange_format_parses_and_displays() {
+        for (text, format) in FORMATS {
+            assert_eq!(text.parse::<ExchangeFormat>().unwrap(), format);
+            assert_eq!(format.to_string(), text);
+        }
+        assert!("json".parse::<ExchangeFormat>().is_err());
+    }
+
+    #[test]
+    fn aliases_are_exported_in_each_format() {
+        let aliases = ["a.local", "b.local"];
+        let export = |format| export_aliases(format, &aliases, "host.local", &addresses());
+        assert_eq!(
+            export(ExchangeFormat::Hosts),
+            "192.168.1.2\ta.local\n192.168.1.2\tb.local\nfe80::1\ta.local\nfe80::1\tb.local\n"
+        );
+        assert_eq!(export(ExchangeFormat::AvahiHosts), export(ExchangeFormat::Hosts));
+        assert_eq!(
+            export(ExchangeFormat::Dnsmasq),
+            "host-record=a.local,192.168.1.2,fe80::1\nhost-record=b.local,192.168.1.2,fe80::1\n"
+        );
+        assert_eq!(
+            export(ExchangeFormat::BindZone),
+            concat!(
+                "host.local.\t60\tIN\tA\t192.168.1.2\n",
+                "host.local.\t60\tIN\tAAAA\tfe80::1\n",
+                "a.local.\t60\tIN\tCNAME\thost.local.\n",
+                "b.local.\t60\tIN\tCNAME\thost.local.\n",
+            )
+        );
+        assert_eq!(export(ExchangeFormat::Aliases), "a.local\nb.local\n");
+    }
+
+    #[test]
+    fn exported_aliases_are_imported() {
+        let aliases = ["a.local", "b.local"];
+        for (_, format) in FORMATS {
+            let text = export_aliases(format, &aliases, "host.local", &addresses());
+            assert_eq!(import_names(format, &text), aliases, "{}", format);
+        }
+    }
+
+    #[test]
+    fn only_local_names_are_imported() {
+        let hosts = "# Comment\n127.0.0.1 localhost\n10.0.0.1 nas NAS.local nas.lan # NAS\n";
+        assert_eq!(import_names(ExchangeFormat::Hosts, hosts), vec!["nas.local"]);
+        let dnsmasq =
+            "address=/a.local/b.local/10.0.0.1\ncname=c.local,d.local,nas.lan\nport=53\n";
+        assert_eq!(import_names(ExchangeFormat::Dnsmasq, dnsmasq), vec![
+            "a.local", "b.local", "c.local", "d.local"
+        ]);
+        let zone =
+            "$TTL 60\n; Comment\na.local. IN CNAME nas.local.\nnas.local. IN A 10.0.0.1\n";
+        assert_eq!(import_names(ExchangeFormat::BindZone, zone), vec!["a.local"]);
+        let aliases = "a.local # A\n\nb.lan\na.local\n";
+        assert_eq!(import_names(ExchangeFormat::Aliases, aliases), vec!["a.local"]);
+    }
+}
+
+// end
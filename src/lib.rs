@@ -5,19 +5,36 @@
 use std::time;
 
 mod alias;
-pub use alias::{is_valid_alias, new_alias, validate_aliases, Alias};
+pub use alias::{
+    is_valid_alias, new_alias, new_alias_or_template, validate_aliases,
+    validate_aliases_or_templates, Alias,
+};
 mod aliases_file;
-pub use aliases_file::AliasesFile;
+pub use aliases_file::{AliasesFile, Preview, FILE_TEMPLATE};
 pub mod avahi_dbus;
+pub mod check;
+pub mod completions;
+pub mod diff;
+pub mod doctor;
 pub mod encoding;
+pub mod expiry;
+pub mod format;
+pub mod glob;
+pub mod history;
+pub mod interchange;
 // mod error;
 // pub use error::ErrorWrapper;
 mod line;
-pub use line::Line;
+pub use line::{is_valid_attribute, Line, OWNER_ATTRIBUTE};
 mod logging;
 pub use logging::{init_console_logging, init_syslog_logging};
+pub mod lookup;
 mod options;
-pub use options::{Command, CommandOpts, DaemonOpts};
+pub use options::{Command, CommandOpts, DaemonOpts, GroupCommand};
+pub mod output;
+pub mod policy;
+pub mod publish;
+pub mod template;
 
 /// Default server timeout
 pub const DEFAULT_TIMEOUT: time::Duration = time::Duration::from_secs(60);
@@ -1,32 +1,221 @@
 #![warn(clippy::all)]
 
-use crate::alias::{new_alias, Alias};
+use time::OffsetDateTime;
 
-#[derive(Debug)]
+use crate::alias::{is_valid_alias, new_alias_or_template, Alias};
+use crate::expiry::{self, EXPIRES_ATTRIBUTE};
+use crate::glob::glob_matches;
+use crate::template::{self, Variables};
+
+#[derive(Clone, Debug)]
 pub struct Line {
-    line: String, // cov(skip)
+    line: String,             // cov(skip)
+    expanded: Option<String>, // cov(skip)
 }
 
+/// The marker that disables an alias (or group) without removing it from the file
+pub const DISABLED_MARKER: char = '!';
+
+/// The marker that starts a host selector (e.g., `grafana.local @monitor-*`)
+pub const SELECTOR_MARKER: char = '@';
+
+/// The attribute naming who owns an alias (e.g., `a.local # Grafana owner=team-x`)
+pub const OWNER_ATTRIBUTE: &str = "owner";
+
 impl Line {
-    pub fn new(line: String) -> Self { Self { line } }
+    pub fn new(line: String) -> Self { Self { line, expanded: None } }
     pub fn alias(&self) -> Option<Alias<'_>> {
         let raw_alias = clean_alias(&self.line);
-        raw_alias.map(new_alias)
+        match &self.expanded {
+            Some(expanded) if raw_alias.is_some() => match is_valid_alias(expanded) {
+                true => Some(Ok(expanded)),
+                false => Some(Err(expanded)),
+            },
+            _ => raw_alias.map(new_alias_or_template),
+        }
     }
     pub fn text(&self) -> &str { &self.line }
+
+    /// Returns the comment (the text following the first `#`), if any.
+    pub fn comment(&self) -> Option<&str> { self.line.find('#').map(|i| &self.line[i + 1..]) }
+
+    /// Returns the comment without its attributes (trimmed), if any.
+    pub fn description(&self) -> Option<&str> {
+        let description = split_attributes(self.comment()?).0;
+        (!description.is_empty()).then_some(description)
+    }
+
+    /// Returns the attributes: the `key=value` words ending the comment (e.g., `owner=team-x`
+    /// in `a.local # Grafana owner=team-x`).
+    pub fn attributes(&self) -> Vec<(&str, &str)> {
+        self.comment().map_or(vec![], |comment| split_attributes(comment).1)
+    }
+
+    /// Returns the value of an attribute (see `attributes`), if present.
+    pub fn attribute(&self, key: &str) -> Option<&str> {
+        self.attributes().into_iter().find(|(k, _)| *k == key).map(|(_, value)| value)
+    }
+
+    /// Returns the time the alias expires (the `expires` attribute), if it has a valid one.
+    pub fn expires(&self) -> Option<OffsetDateTime> {
+        self.attribute(EXPIRES_ATTRIBUTE).and_then(expiry::parse_timestamp)
+    }
+
+    /// Returns `true` if the alias has expired at `now`.
+    pub fn is_expired(&self, now: OffsetDateTime) -> bool {
+        self.expires().is_some_and(|expires| expires <= now)
+    }
+
+    /// Create an alias line with a comment made of a description and attributes (see
+    /// `is_valid_attribute`).
+    pub fn with_comment(
+        alias: &str, description: Option<&str>, attributes: &[(&str, &str)],
+    ) -> Self {
+        let comment: Vec<String> = description
+            .map(str::to_owned)
+            .into_iter()
+            .chain(attributes.iter().map(|(key, value)| format!("{}={}", key, value)))
+            .collect();
+        match comment.is_empty() {
+            true => Line::new(alias.to_owned()),
+            false => Line::new(format!("{} # {}", alias, comment.join(" "))),
+        }
+    }
+
+    /// Returns the alias template (e.g., `{hostname}-grafana.local`), if the line has one.
+    pub fn template(&self) -> Option<&str> {
+        clean_alias(&self.line).filter(|alias| template::is_template(alias))
+    }
+
+    /// Expand the alias template (if any) using `variables`. After expansion, `alias` returns
+    /// the expanded alias, which is valid only if it passes `is_valid_alias`. A template that
+    /// cannot be expanded yields an invalid alias (the template itself).
+    pub fn expand(&mut self, variables: &Variables) {
+        self.expanded = self.template().map(|template| {
+            template::expand(template, variables).unwrap_or_else(|_| template.to_owned())
+        });
+    }
+
+    /// Returns the group name if the line is a group header (e.g., `[docker-stack]`).
+    pub fn group(&self) -> Option<&str> { clean_group(&self.line) }
+
+    /// Returns the host selectors (glob patterns) following the alias or group header,
+    /// without the `@`.
+    pub fn selectors(&self) -> Vec<&str> { split_selectors(enabled_content(&self.line)).1 }
+
+    /// Returns `true` if the line applies to the host: the line has no host selectors or
+    /// one of them matches `host_name`.
+    pub fn applies_to(&self, host_name: &str) -> bool {
+        let selectors = self.selectors();
+        selectors.is_empty() || selectors.iter().any(|s| glob_matches(s, host_name))
+    }
+
+    /// Returns `true` if the line contains an alias or group header marked disabled (e.g.,
+    /// `!a.local` or `![docker-stack]`).
+    pub fn is_disabled(&self) -> bool {
+        content(&self.line).starts_with(DISABLED_MARKER)
+            && (self.alias().is_some() || self.group().is_some())
+    }
+
+    /// Mark the alias or group header on this line as disabled or enabled. Leading
+    /// whitespace and trailing comments are preserved. Other lines are not changed.
+    pub fn set_disabled(&mut self, disabled: bool) {
+        if (self.alias().is_none() && self.group().is_none()) || self.is_disabled() == disabled
+        {
+            return;
+        }
+        let start = self.line.len() - self.line.trim_start().len();
+        if disabled {
+            self.line.insert(start, DISABLED_MARKER);
+        } else {
+            self.line.remove(start);
+        }
+    }
+
+    /// Replace the alias on this line with `alias`. The disabled marker, host selectors,
+    /// and comment are preserved. Lines without an alias are not changed.
+    pub fn set_alias(&mut self, alias: &str) {
+        if let Some(raw_alias) = clean_alias(&self.line) {
+            let start = raw_alias.as_ptr() as usize - self.line.as_ptr() as usize;
+            self.line.replace_range(start..start + raw_alias.len(), alias);
+            self.expanded = None;
+        }
+    }
 }
 
-fn clean_alias(raw_alias: &str) -> Option<&str> {
-    let line_without_comment = match raw_alias.find('#') {
-        Some(i) => &raw_alias[0..i],
-        None => raw_alias,
+/// Return the line without its comment or leading and trailing whitespace.
+fn content(line: &str) -> &str {
+    let line_without_comment = match line.find('#') {
+        Some(i) => &line[0..i],
+        None => line,
     };
-    match line_without_comment.trim() {
-        empty if empty.is_empty() => None,
+    line_without_comment.trim()
+}
+
+fn enabled_content(line: &str) -> &str {
+    let content = content(line);
+    content.strip_prefix(DISABLED_MARKER).map(str::trim_start).unwrap_or(content)
+}
+
+/// Split trailing host selectors (e.g., `@monitor-01`) from the rest of the content.
+fn split_selectors(content: &str) -> (&str, Vec<&str>) {
+    let mut rest = content;
+    let mut selectors = Vec::new();
+    while let Some((head, tail)) = rest.rsplit_once(char::is_whitespace) {
+        match tail.strip_prefix(SELECTOR_MARKER) {
+            Some(selector) if !selector.is_empty() && !head.trim().is_empty() => {
+                selectors.insert(0, selector);
+                rest = head.trim_end();
+            },
+            _ => break,
+        }
+    }
+    (rest, selectors)
+}
+
+/// Returns `true` if `key=value` is a valid attribute: the key is lowercase letters, digits,
+/// and hyphens, and the value is not empty and has no whitespace or `#`.
+pub fn is_valid_attribute(key: &str, value: &str) -> bool {
+    !key.is_empty()
+        && key.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+        && !value.is_empty()
+        && !value.contains(|c: char| c.is_whitespace() || c == '#')
+}
+
+/// Split the trailing attributes from the rest of a comment.
+fn split_attributes(comment: &str) -> (&str, Vec<(&str, &str)>) {
+    let mut rest = comment.trim();
+    let mut attributes = Vec::new();
+    while !rest.is_empty() {
+        let (head, word) = rest.rsplit_once(char::is_whitespace).unwrap_or(("", rest));
+        match word.split_once('=') {
+            Some((key, value)) if is_valid_attribute(key, value) => {
+                attributes.insert(0, (key, value));
+                rest = head.trim_end();
+            },
+            _ => break,
+        }
+    }
+    (rest, attributes)
+}
+
+fn clean_alias(raw_alias: &str) -> Option<&str> {
+    match split_selectors(enabled_content(raw_alias)).0 {
+        "" => None,
+        _ if clean_group(raw_alias).is_some() => None,
         alias => Some(alias),
     }
 }
 
+fn clean_group(raw_group: &str) -> Option<&str> {
+    let content = split_selectors(enabled_content(raw_group)).0;
+    let group = content.strip_prefix('[')?.strip_suffix(']')?.trim();
+    match group {
+        "" => None,
+        group => Some(group),
+    }
+}
+
 //**********************************************************************************************
 // Unit tests
 //**********************************************************************************************
@@ -34,6 +223,7 @@ fn clean_alias(raw_alias: &str) -> Option<&str> {
 #[cfg(test)]
 mod tests {
     use super::Line;
+    use crate::template;
 
     // Utility macros to simplify tests
     #[allow(unused_macros)] // work around compiler bug
@@ -126,6 +316,219 @@ mod tests {
             assert!(option_from_text!(text).is_none())
         }
     }
+
+    #[test]
+    fn disabled_aliases_yield_alias() {
+        let data = ["!a.local", " !a.local", "! a.local", "!a.local # Comment"];
+        for text in data {
+            assert_eq!(alias_from_text!(text).unwrap(), "a.local");
+            assert!(Line::new(String::from(text)).is_disabled());
+        }
+    }
+
+    #[test]
+    fn enabled_aliases_are_not_disabled() {
+        let data = ["a.local", " a.local", "a.local # !Comment", "# !a.local", "!", " ! "];
+        for text in data {
+            assert!(!Line::new(String::from(text)).is_disabled());
+        }
+    }
+
+    #[test]
+    fn set_disabled_toggles_in_place() {
+        let data = [
+            ("a.local", "!a.local"),
+            ("  a.local # Comment", "  !a.local # Comment"),
+            ("\ta.local", "\t!a.local"),
+        ];
+        for (enabled, disabled) in data {
+            let mut line = Line::new(String::from(enabled));
+            line.set_disabled(true);
+            assert_eq!(line.text(), disabled);
+            line.set_disabled(true);
+            assert_eq!(line.text(), disabled);
+            line.set_disabled(false);
+            assert_eq!(line.text(), enabled);
+        }
+    }
+
+    #[test]
+    fn group_headers_yield_group() {
+        let data = ["[web]", " [web] ", "[ web ]", "[web] # Comment", "![web]", " ! [web]"];
+        for text in data {
+            let line = Line::new(String::from(text));
+            assert_eq!(line.group(), Some("web"));
+            assert!(line.alias().is_none());
+        }
+    }
+
+    #[test]
+    fn non_headers_yield_no_group() {
+        for text in ["", "a.local", "# [web]", "[]", "[web", "web]", "!a.local"] {
+            assert!(Line::new(String::from(text)).group().is_none());
+        }
+    }
+
+    #[test]
+    fn disabled_group_headers_are_disabled() {
+        assert!(Line::new(String::from("![web]")).is_disabled());
+        assert!(!Line::new(String::from("[web]")).is_disabled());
+        let mut line = Line::new(String::from("[web] # Comment"));
+        line.set_disabled(true);
+        assert_eq!(line.text(), "![web] # Comment");
+        line.set_disabled(false);
+        assert_eq!(line.text(), "[web] # Comment");
+    }
+
+    #[test]
+    fn host_selectors_are_not_part_of_the_alias() {
+        let data = [
+            ("a.local @monitor-01", vec!["monitor-01"]),
+            ("a.local\t@monitor-*  # Comment", vec!["monitor-*"]),
+            ("!a.local @web-01 @web-02", vec!["web-01", "web-02"]),
+            ("a.local", vec![]),
+            ("a.local # @monitor-01", vec![]),
+        ];
+        for (text, selectors) in data {
+            let line = Line::new(String::from(text));
+            assert_eq!(line.alias().unwrap().unwrap(), "a.local");
+            assert_eq!(line.selectors(), selectors);
+        }
+    }
+
+    #[test]
+    fn malformed_selectors_are_part_of_the_alias() {
+        for text in ["@monitor-01", "a.local @", "a.local@monitor-01"] {
+            let line = Line::new(String::from(text));
+            assert!(line.selectors().is_empty());
+            assert!(line.alias().unwrap().is_err());
+        }
+    }
+
+    #[test]
+    fn applies_to_matches_host_selectors() {
+        let line = Line::new(String::from("a.local @monitor-* @web-01"));
+        assert!(line.applies_to("monitor-01"));
+        assert!(line.applies_to("web-01"));
+        assert!(!line.applies_to("web-02"));
+        assert!(Line::new(String::from("a.local")).applies_to("web-02"));
+        let line = Line::new(String::from("[web] @web-01"));
+        assert_eq!(line.group(), Some("web"));
+        assert!(line.applies_to("web-01"));
+        assert!(!line.applies_to("web-02"));
+    }
+
+    #[test]
+    fn templates_yield_template_until_expanded() {
+        let mut line = Line::new(String::from("!{hostname}-db.local @db-* # Comment"));
+        assert_eq!(line.template(), Some("{hostname}-db.local"));
+        assert_eq!(line.alias().unwrap().unwrap(), "{hostname}-db.local");
+        line.expand(&template::host_variables("db-01"));
+        assert_eq!(line.alias().unwrap().unwrap(), "db-01-db.local");
+        assert_eq!(line.text(), "!{hostname}-db.local @db-* # Comment");
+        assert!(line.is_disabled());
+    }
+
+    #[test]
+    fn invalid_expansions_yield_invalid_aliases() {
+        let data = [("{site}-db.local", "{site}-db.local"), ("{hostname}.local", "x_y.local")];
+        for (text, invalid_alias) in data {
+            let mut line = Line::new(String::from(text));
+            line.expand(&template::host_variables("x_y"));
+            assert_eq!(line.alias().unwrap().unwrap_err(), invalid_alias);
+        }
+    }
+
+    #[test]
+    fn expand_ignores_lines_without_templates() {
+        for text in ["a.local", "# {hostname}.local", "[web]"] {
+            let mut line = Line::new(String::from(text));
+            let alias = format!("{:?}", line.alias());
+            line.expand(&template::host_variables("x"));
+            assert_eq!(format!("{:?}", line.alias()), alias);
+        }
+    }
+
+    #[test]
+    fn comment_yields_text_after_first_pound_sign() {
+        let data = [
+            ("a.local # Comment", Some(" Comment")),
+            ("# Comment # more", Some(" Comment # more")),
+            ("a.local#", Some("")),
+            ("a.local", None),
+        ];
+        for (text, comment) in data {
+            assert_eq!(Line::new(String::from(text)).comment(), comment);
+        }
+    }
+
+    #[test]
+    fn set_disabled_ignores_lines_without_aliases() {
+        for text in ["", "  ", "# Comment"] {
+            let mut line = Line::new(String::from(text));
+            line.set_disabled(true);
+            assert_eq!(line.text(), text);
+        }
+    }
+
+    #[test]
+    fn attributes_are_parsed_from_the_end_of_the_comment() {
+        let data = [
+            ("a.local", None, vec![]),
+            ("a.local # Grafana", Some("Grafana"), vec![]),
+            ("a.local # Grafana owner=team-x", Some("Grafana"), vec![("owner", "team-x")]),
+            ("a.local #owner=x expires=1", None, vec![("owner", "x"), ("expires", "1")]),
+            ("a.local # a=b is text owner=x", Some("a=b is text"), vec![("owner", "x")]),
+            ("a.local # Owner=x", Some("Owner=x"), vec![]),
+        ];
+        for (text, description, attributes) in data {
+            let line = Line::new(String::from(text));
+            assert_eq!(line.description(), description);
+            assert_eq!(line.attributes(), attributes);
+        }
+        let line = Line::new(String::from("a.local # owner=team-x"));
+        assert_eq!(line.attribute("owner"), Some("team-x"));
+        assert_eq!(line.attribute("expires"), None);
+    }
+
+    #[test]
+    fn expires_parses_the_expires_attribute() {
+        let now = crate::expiry::parse_timestamp("2026-10-18T14:00:00Z").unwrap();
+        let line = Line::new(String::from("a.local # Demo expires=2026-10-18T16:00:00Z"));
+        assert_eq!(line.expires(), crate::expiry::parse_timestamp("2026-10-18T16:00:00Z"));
+        assert!(!line.is_expired(now));
+        assert!(line.is_expired(now + time::Duration::hours(2)));
+        for text in ["a.local", "a.local # expires=soon"] {
+            let line = Line::new(String::from(text));
+            assert_eq!(line.expires(), None);
+            assert!(!line.is_expired(now));
+        }
+    }
+
+    #[test]
+    fn with_comment_round_trips() {
+        let line = Line::with_comment("a.local", Some("Grafana for X"), &[("owner", "team-x")]);
+        assert_eq!(line.text(), "a.local # Grafana for X owner=team-x");
+        assert_eq!(line.description(), Some("Grafana for X"));
+        assert_eq!(line.attribute("owner"), Some("team-x"));
+        assert_eq!(Line::with_comment("a.local", None, &[]).text(), "a.local");
+    }
+
+    #[test]
+    fn set_alias_preserves_the_rest_of_the_line() {
+        let data = [
+            ("a.local", "b.local"),
+            ("  a.local   # Comment", "  b.local   # Comment"),
+            ("! a.local @host-* # Comment", "! b.local @host-* # Comment"),
+            ("# Comment", "# Comment"),
+            ("[web]", "[web]"),
+        ];
+        for (text, renamed) in data {
+            let mut line = Line::new(String::from(text));
+            line.set_alias("b.local");
+            assert_eq!(line.text(), renamed);
+        }
+    }
 }
 
 // end
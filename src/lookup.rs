@@ -0,0 +1,646 @@
+//! Look up names through Avahi
+//!
+//! Resolve names and addresses with Avahi's host name and address resolvers, and decide
+//! whether an alias resolves to the local host (for `avahi-alias status`, `doctor`, and the
+//! conflict check of `add`). Record browsers report the CNAME chains and addresses shown by
+//! `avahi-alias resolve`.
+
+#![warn(clippy::all)]
+
+use std::collections::HashSet;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use dbus::message::MatchRule;
+
+use crate::avahi_dbus::{self, avahi, OrgFreedesktopAvahiServer};
+use crate::encoding;
+use crate::output::Value;
+
+/// The longest CNAME chain `resolve` follows
+const MAX_CNAME_CHAIN: usize = 8;
+
+/// How long to wait for Avahi to report the records of a name
+const BROWSE_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// The D-Bus errors Avahi's resolvers report when no host answers for a name
+const NOT_FOUND_ERRORS: [&str; 2] =
+    ["org.freedesktop.Avahi.TimeoutError", "org.freedesktop.Avahi.NotFoundError"];
+
+/// A host name or address resolved by Avahi
+pub struct Resolution {
+    pub interface: i32,
+    pub protocol: i32,
+    pub name: String,
+    pub address: String,
+    pub flags: u32,
+}
+
+/// Resolve a host name (e.g., an alias) to an address using Avahi. `protocol` selects the
+/// address protocol (IPv4, IPv6, or either).
+pub fn resolve_host_name(
+    avahi_server_proxy: &avahi_dbus::DBusProxy<'_, '_>, name: &str, protocol: avahi::Protocol,
+) -> Result<Resolution> {
+    let (interface, protocol, name, _, address, flags) = avahi_server_proxy
+        .resolve_host_name(
+            avahi::Interface::UNSPECIFIED as i32,
+            avahi::Protocol::UNSPEC as i32,
+            name,
+            protocol as i32,
+            0,
+        )
+        .with_context(|| format!("could not resolve {:?}", name))?;
+    Ok(Resolution { interface, protocol, name, address, flags })
+}
+
+/// Resolve an address to a host name using Avahi.
+pub fn resolve_address(
+    avahi_server_proxy: &avahi_dbus::DBusProxy<'_, '_>, address: &str,
+) -> Result<Resolution> {
+    let (interface, protocol, _, address, name, flags) = avahi_server_proxy
+        .resolve_address(
+            avahi::Interface::UNSPECIFIED as i32,
+            avahi::Protocol::UNSPEC as i32,
+            address,
+            0,
+        )
+        .with_context(|| format!("could not resolve {:?}", address))?;
+    Ok(Resolution { interface, protocol, name, address, flags })
+}
+
+/// The local host, as named and addressed by Avahi
+pub struct LocalHost {
+    fqdn: String,
+    addresses: HashSet<String>,
+}
+
+impl LocalHost {
+    pub fn new(avahi_server_proxy: &avahi_dbus::DBusProxy<'_, '_>) -> Result<Self> {
+        let fqdn = avahi_server_proxy.get_host_name_fqdn()?;
+        let addresses = [avahi::Protocol::INET, avahi::Protocol::INET6]
+            .into_iter()
+            .filter_map(|protocol| resolve_host_name(avahi_server_proxy, &fqdn, protocol).ok())
+            .map(|resolution| resolution.address)
+            .collect();
+        Ok(Self { fqdn, addresses })
+    }
+
+    /// Returns `true` if a name resolved to the local host. `host` is the host name the
+    /// resolved address belongs to (if known).
+    pub fn owns(&self, resolution: &Resolution, host: Option<&str>) -> bool {
+        let local_flags =
+            avahi::LookupResultFlags::LOCAL as u32 | avahi::LookupResultFlags::OUR_OWN as u32;
+        resolution.flags & local_flags != 0
+            || self.addresses.contains(&resolution.address)
+            || host.is_some_and(|host| host.eq_ignore_ascii_case(&self.fqdn))
+    }
+}
+
+/// Returns `true` if an error reports that no host answered for a name (rather than a failure
+/// to ask Avahi).
+pub fn is_not_found(error: &anyhow::Error) -> bool {
+    error
+        .downcast_ref::<dbus::Error>()
+        .and_then(dbus::Error::name)
+        .is_some_and(|name| NOT_FOUND_ERRORS.contains(&name))
+}
+
+/// Find the host claiming `alias` on the network, as `host (address)`. Returns `None` if no
+/// host answers for the alias or it resolves to the local host.
+pub fn claimant(
+    avahi_server_proxy: &avahi_dbus::DBusProxy<'_, '_>, local_host: &LocalHost, alias: &str,
+) -> Result<Option<String>> {
+    let resolution = match resolve_host_name(avahi_server_proxy, alias, avahi::Protocol::UNSPEC)
+    {
+        Ok(resolution) => resolution,
+        Err(error) if is_not_found(&error) => return Ok(None),
+        Err(error) => return Err(error),
+    };
+    let host = resolve_address(avahi_server_proxy, &resolution.address).ok().map(|r| r.name);
+    if local_host.owns(&resolution, host.as_deref()) {
+        return Ok(None);
+    }
+    Ok(Some(match host {
+        Some(host) => format!("{} ({})", host, resolution.address),
+        None => resolution.address,
+    }))
+}
+
+/// The network status of an alias
+pub struct AliasStatus {
+    pub alias: String,
+    /// `published` (resolves to this host), `conflict` (resolves to another host), or
+    /// `unresolved`
+    pub status: &'static str,
+    pub host: Option<String>,
+    pub address: Option<String>,
+    pub interface: Option<String>,
+    /// The time taken to resolve the alias
+    pub milliseconds: usize,
+    pub error: Option<String>,
+}
+
+impl AliasStatus {
+    /// The status of an alias resolved to an address belonging to `host` (if known) on
+    /// `interface`.
+    fn resolved(
+        alias: &str, local_host: &LocalHost, resolution: Resolution, host: Option<String>,
+        interface: Option<String>, milliseconds: usize,
+    ) -> Self {
+        let status = match local_host.owns(&resolution, host.as_deref()) {
+            true => "published",
+            false => "conflict",
+        };
+        Self {
+            alias: alias.to_owned(),
+            status,
+            host,
+            address: Some(resolution.address),
+            interface,
+            milliseconds,
+            error: None,
+        }
+    }
+
+    /// The status of an alias that could not be resolved.
+    fn unresolved(alias: &str, error: &anyhow::Error, milliseconds: usize) -> Self {
+        Self {
+            alias: alias.to_owned(),
+            status: "unresolved",
+            host: None,
+            address: None,
+            interface: None,
+            milliseconds,
+            error: Some(format!("{:#}", error)),
+        }
+    }
+
+    /// The status as a line of text, with the alias padded to `width`.
+    pub fn to_line(&self, width: usize) -> String {
+        let detail = match (&self.error, &self.host, &self.address) {
+            (Some(error), ..) => error.clone(),
+            (None, Some(host), Some(address)) => format!("{} ({})", host, address),
+            (None, None, Some(address)) => address.clone(),
+            _ => String::new(),
+        };
+        let interface = self.interface.as_ref().map_or(String::new(), |i| format!(" on {}", i));
+        format!(
+            "{:width$}  {:10}  {}{} [{} ms]",
+            self.alias,
+            self.status,
+            detail,
+            interface,
+            self.milliseconds,
+            width = width
+        )
+    }
+
+    pub fn to_record(&self) -> Vec<Value> {
+        vec![
+            Value::Text(self.alias.clone()),
+            Value::Text(self.status.to_owned()),
+            Value::optional_text(self.host.as_deref()),
+            Value::optional_text(self.address.as_deref()),
+            Value::optional_text(self.interface.as_deref()),
+            Value::Number(self.milliseconds),
+            Value::optional_text(self.error.as_deref()),
+        ]
+    }
+}
+
+/// Resolve an alias through Avahi and report whether it resolves to the local host.
+pub fn alias_status(
+    avahi_server_proxy: &avahi_dbus::DBusProxy<'_, '_>, local_host: &LocalHost, alias: &str,
+) -> AliasStatus {
+    log::debug!("Resolving {:?}", alias);
+    let start = Instant::now();
+    let result = resolve_host_name(avahi_server_proxy, alias, avahi::Protocol::UNSPEC);
+    let milliseconds = start.elapsed().as_millis() as usize;
+    match result {
+        Ok(resolution) => {
+            let host =
+                resolve_address(avahi_server_proxy, &resolution.address).ok().map(|r| r.name);
+            let interface = avahi_server_proxy
+                .get_network_interface_name_by_index(resolution.interface)
+                .ok();
+            AliasStatus::resolved(alias, local_host, resolution, host, interface, milliseconds)
+        },
+        Err(error) => AliasStatus::unresolved(alias, &error, milliseconds),
+    }
+}
+
+/// A DNS record reported by an Avahi record browser
+pub struct BrowsedRecord {
+    pub interface: i32,
+    pub protocol: i32,
+    pub rdata: Vec<u8>,
+}
+
+/// An answer reported by `resolve`
+#[derive(Debug, PartialEq, Eq)]
+pub struct Answer {
+    pub name: String,
+    /// `PTR`, `CNAME`, `A`, or `AAAA`
+    pub record_type: &'static str,
+    pub value: String,
+    pub interface: String,
+    pub protocol: &'static str,
+}
+
+impl Answer {
+    /// The answer as a sentence (e.g., `a.local is an alias for gandalf.local on eth0
+    /// (IPv4)`).
+    pub fn to_line(&self) -> String {
+        let description = match self.record_type {
+            "PTR" => "is the address of",
+            "CNAME" => "is an alias for",
+            _ => "has address",
+        };
+        format!(
+            "{} {} {} on {} ({})",
+            self.name, description, self.value, self.interface, self.protocol
+        )
+    }
+
+    pub fn to_record(&self) -> Vec<Value> {
+        vec![
+            Value::Text(self.name.clone()),
+            Value::Text(self.record_type.to_owned()),
+            Value::Text(self.value.clone()),
+            Value::Text(self.interface.clone()),
+            Value::Text(self.protocol.to_owned()),
+        ]
+    }
+}
+
+/// Look up a name or address through Avahi: the host name of an address, the CNAME chain of
+/// the name, and the addresses at the end of the chain (per interface and protocol).
+pub fn resolve(
+    dbus_connection: &dbus::blocking::Connection, name_or_address: &str,
+) -> Result<Vec<Answer>> {
+    let avahi_server_proxy = server_proxy(dbus_connection);
+    let interface_name = |index: i32| {
+        avahi_server_proxy.get_network_interface_name_by_index(index).unwrap_or_default()
+    };
+    let mut answers = Vec::<Answer>::new();
+    // Resolve an address to its host name; then resolve the host name.
+    let mut name = name_or_address.to_owned();
+    if name_or_address.parse::<IpAddr>().is_ok() {
+        let resolution = resolve_address(&avahi_server_proxy, name_or_address)?;
+        answers.push(Answer {
+            name: name_or_address.to_owned(),
+            record_type: "PTR",
+            value: resolution.name.clone(),
+            interface: interface_name(resolution.interface),
+            protocol: protocol_name(resolution.protocol),
+        });
+        name = resolution.name;
+    }
+    let browse = |name: &str, record_type| browse_records(dbus_connection, name, record_type);
+    let name = follow_cnames(
+        &name,
+        |name| browse(name, avahi::RecordType::CNAME),
+        &interface_name,
+        &mut answers,
+    )?;
+    answers.extend(address_answers(
+        &name,
+        browse(&name, avahi::RecordType::A)?,
+        &interface_name,
+    ));
+    answers.extend(address_answers(
+        &name,
+        browse(&name, avahi::RecordType::AAA)?,
+        &interface_name,
+    ));
+    if !answers.iter().any(|a| a.record_type == "A" || a.record_type == "AAAA") {
+        // Not browsable (e.g., a static host); ask the host name resolver
+        let resolution =
+            resolve_host_name(&avahi_server_proxy, &name, avahi::Protocol::UNSPEC)?;
+        let record_type = match resolution.address.parse::<IpAddr>() {
+            Ok(IpAddr::V6(_)) => "AAAA",
+            _ => "A",
+        };
+        answers.push(Answer {
+            name: name.clone(),
+            record_type,
+            value: resolution.address,
+            interface: interface_name(resolution.interface),
+            protocol: protocol_name(resolution.protocol),
+        });
+    }
+    Ok(answers)
+}
+
+/// Follow the CNAME chain from `name` (up to `MAX_CNAME_CHAIN` records, stopping at loops),
+/// adding an answer for each record. `browse` returns the CNAME records of a name. Returns
+/// the last name in the chain.
+fn follow_cnames(
+    name: &str, mut browse: impl FnMut(&str) -> Result<Vec<BrowsedRecord>>,
+    interface_name: &impl Fn(i32) -> String, answers: &mut Vec<Answer>,
+) -> Result<String> {
+    let mut name = name.to_owned();
+    let mut names = vec![name.clone()];
+    while names.len() <= MAX_CNAME_CHAIN {
+        let records = browse(&name)?;
+        let target = match records.first().and_then(|r| encoding::decode_rdata_name(&r.rdata)) {
+            Some(target) => target,
+            None => break,
+        };
+        for record in records.iter() {
+            answers.push(Answer {
+                name: name.clone(),
+                record_type: "CNAME",
+                value: target.clone(),
+                interface: interface_name(record.interface),
+                protocol: protocol_name(record.protocol),
+            });
+        }
+        if names.contains(&target) {
+            log::warn!("CNAME loop at {:?}", target);
+            break;
+        }
+        names.push(target.clone());
+        name = target;
+    }
+    Ok(name)
+}
+
+/// The answers for the A or AAAA records of `name` (skipping malformed records).
+fn address_answers(
+    name: &str, records: Vec<BrowsedRecord>, interface_name: &impl Fn(i32) -> String,
+) -> Vec<Answer> {
+    records
+        .into_iter()
+        .filter_map(|record| {
+            let address = encoding::decode_rdata_address(&record.rdata)?;
+            Some(Answer {
+                name: name.to_owned(),
+                record_type: match address {
+                    IpAddr::V4(_) => "A",
+                    IpAddr::V6(_) => "AAAA",
+                },
+                value: address.to_string(),
+                interface: interface_name(record.interface),
+                protocol: protocol_name(record.protocol),
+            })
+        })
+        .collect()
+}
+
+/// Browse the records of a name using an Avahi record browser. Returns once Avahi has
+/// reported every record it currently knows of (or the browse times out).
+pub fn browse_records(
+    dbus_connection: &dbus::blocking::Connection, name: &str, record_type: avahi::RecordType,
+) -> Result<Vec<BrowsedRecord>> {
+    type ItemNew = (i32, i32, String, u16, u16, Vec<u8>, u32);
+    // Avahi signals new browsers immediately, so the matches are added before the browser
+    // is created and the signals are filtered by browser path afterwards.
+    let items = Arc::new(Mutex::new(Vec::<(String, ItemNew)>::new()));
+    let finished = Arc::new(Mutex::new(HashSet::<String>::new()));
+    let signal = |member: &'static str| {
+        MatchRule::new_signal(avahi::AVAHI_DBUS_INTERFACE_RECORD_BROWSER, member)
+    };
+    let item_token = {
+        let items = items.clone();
+        dbus_connection.add_match(signal("ItemNew"), move |item: ItemNew, _, message| {
+            let path = message.path().map(|p| p.to_string()).unwrap_or_default();
+            items.lock().unwrap().push((path, item));
+            true
+        })?
+    };
+    let mut tokens = vec![item_token];
+    for member in ["AllForNow", "Failure"] {
+        let finished = finished.clone();
+        tokens.push(dbus_connection.add_match(signal(member), move |_: (), _, message| {
+            let path = message.path().map(|p| p.to_string()).unwrap_or_default();
+            finished.lock().unwrap().insert(path);
+            true
+        })?);
+    }
+    let browser_path = server_proxy(dbus_connection)
+        .record_browser_new(
+            avahi::Interface::UNSPECIFIED as i32,
+            avahi::Protocol::UNSPEC as i32,
+            name,
+            avahi::RecordClass::IN as u16,
+            record_type as u16,
+            0,
+        )
+        .with_context(|| format!("could not browse {:?}", name))?;
+    let path = browser_path.to_string();
+    let start = Instant::now();
+    while !finished.lock().unwrap().contains(&path) && start.elapsed() < BROWSE_TIMEOUT {
+        dbus_connection.process(BROWSE_TIMEOUT.saturating_sub(start.elapsed()))?;
+    }
+    let browser_proxy = dbus_connection.with_proxy(
+        avahi::AVAHI_DBUS_NAME,
+        browser_path,
+        crate::DEFAULT_TIMEOUT,
+    );
+    let _: Result<(), _> =
+        browser_proxy.method_call(avahi::AVAHI_DBUS_INTERFACE_RECORD_BROWSER, "Free", ());
+    for token in tokens {
+        dbus_connection.remove_match(token)?;
+    }
+    let records = items
+        .lock()
+        .unwrap()
+        .drain(..)
+        .filter(|(item_path, _)| *item_path == path)
+        .map(|(_, (interface, protocol, _, _, _, rdata, _))| BrowsedRecord {
+            interface,
+            protocol,
+            rdata,
+        })
+        .collect();
+    Ok(records)
+}
+
+/// The name of an Avahi protocol
+fn protocol_name(protocol: i32) -> &'static str {
+    match protocol {
+        p if p == avahi::Protocol::INET as i32 => "IPv4",
+        p if p == avahi::Protocol::INET6 as i32 => "IPv6",
+        _ => "unspecified",
+    }
+}
+
+fn server_proxy(dbus_connection: &dbus::blocking::Connection) -> avahi_dbus::DBusProxy<'_, '_> {
+    dbus_connection.with_proxy(
+        avahi::AVAHI_DBUS_NAME,
+        avahi::AVAHI_DBUS_PATH_SERVER,
+        crate::DEFAULT_TIMEOUT,
+    )
+}
+
+//**********************************************************************************************
+// Unit tests
+//**********************************************************************************************
+
+#[cfg(test)]
+mod tests {
+    use anyhow::{anyhow, Result};
+
+    use super::{
+        address_answers, follow_cnames, is_not_found, AliasStatus, Answer, BrowsedRecord,
+        LocalHost, Resolution, MAX_CNAME_CHAIN,
+    };
+    use crate::avahi_dbus::avahi;
+    use crate::encoding::encode_rdata;
+    use crate::output::Value;
+
+    fn local_host() -> LocalHost {
+        LocalHost {
+            fqdn: String::from("gandalf.local"),
+            addresses: ["192.168.1.2", "fe80::2"].into_iter().map(str::to_owned).collect(),
+        }
+    }
+
+    fn resolution(address: &str, flags: u32) -> Resolution {
+        Resolution {
+            interface: 2,
+            protocol: avahi::Protocol::INET as i32,
+            name: String::from("a.local"),
+            address: address.to_owned(),
+            flags,
+        }
+    }
+
+    #[test]
+    fn only_resolver_timeouts_mean_not_found() {
+        let error = |name: &str| {
+            anyhow::Error::new(dbus::Error::new_custom(name, "failed"))
+                .context("could not resolve \"a.local\"")
+        };
+        assert!(is_not_found(&error("org.freedesktop.Avahi.TimeoutError")));
+        assert!(is_not_found(&error("org.freedesktop.Avahi.NotFoundError")));
+        assert!(!is_not_found(&error("org.freedesktop.DBus.Error.NoReply")));
+        assert!(!is_not_found(&anyhow!("could not resolve \"a.local\"")));
+    }
+
+    #[test]
+    fn local_host_owns_its_addresses_names_and_local_records() {
+        let local_host = local_host();
+        assert!(local_host.owns(&resolution("192.168.1.2", 0), None));
+        assert!(local_host.owns(&resolution("10.0.0.1", 0), Some("Gandalf.local")));
+        let flags = avahi::LookupResultFlags::OUR_OWN as u32;
+        assert!(local_host.owns(&resolution("10.0.0.1", flags), None));
+        assert!(!local_host.owns(&resolution("10.0.0.1", 0), Some("saruman.local")));
+        assert!(!local_host.owns(&resolution("10.0.0.1", 0), None));
+    }
+
+    #[test]
+    fn resolved_aliases_are_published_or_conflicts() {
+        let local_host = local_host();
+        let status = |address: &str, host: &str| {
+            let resolution = resolution(address, 0);
+            let (host, interface) = (Some(host.to_owned()), Some(String::from("eth0")));
+            AliasStatus::resolved("a.local", &local_host, resolution, host, interface, 5)
+        };
+        let published = status("192.168.1.2", "gandalf.local");
+        assert_eq!(published.status, "published");
+        assert_eq!(
+            published.to_line(9),
+            "a.local    published   gandalf.local (192.168.1.2) on eth0 [5 ms]"
+        );
+        let conflict = status("10.0.0.1", "saruman.local");
+        assert_eq!(conflict.status, "conflict");
+        assert_eq!(conflict.address.as_deref(), Some("10.0.0.1"));
+    }
+
+    fn record(rdata: Vec<u8>) -> BrowsedRecord {
+        BrowsedRecord { interface: 2, protocol: avahi::Protocol::INET as i32, rdata }
+    }
+
+    /// Browse CNAME records from a list of (name, target) pairs.
+    fn browse_cnames<'a>(
+        cnames: &'a [(&'a str, &'a str)],
+    ) -> impl FnMut(&str) -> Result<Vec<BrowsedRecord>> + 'a {
+        move |name| {
+            Ok(cnames
+                .iter()
+                .filter(|(alias, _)| *alias == name)
+                .map(|(_, target)| record(encode_rdata(target)))
+                .collect())
+        }
+    }
+
+    fn interface_name(_: i32) -> String { String::from("eth0") }
+
+    #[test]
+    fn cname_chains_are_followed_to_the_host() {
+        let cnames = [("a.local", "b.local"), ("b.local", "gandalf.local")];
+        let mut answers = Vec::<Answer>::new();
+        let name =
+            follow_cnames("a.local", browse_cnames(&cnames), &interface_name, &mut answers)
+                .unwrap();
+        assert_eq!(name, "gandalf.local");
+        let lines: Vec<String> = answers.iter().map(Answer::to_line).collect();
+        assert_eq!(lines, vec![
+            "a.local is an alias for b.local on eth0 (IPv4)",
+            "b.local is an alias for gandalf.local on eth0 (IPv4)",
+        ]);
+    }
+
+    #[test]
+    fn cname_chains_stop_at_loops_and_the_limit() {
+        let cnames = [("a.local", "b.local"), ("b.local", "a.local")];
+        let mut answers = Vec::<Answer>::new();
+        let name =
+            follow_cnames("a.local", browse_cnames(&cnames), &interface_name, &mut answers)
+                .unwrap();
+        assert_eq!((name.as_str(), answers.len()), ("b.local", 2));
+
+        let names: Vec<String> = (0..20).map(|n| format!("a{}.local", n)).collect();
+        let cnames: Vec<(&str, &str)> =
+            names.windows(2).map(|pair| (pair[0].as_str(), pair[1].as_str())).collect();
+        let mut answers = Vec::<Answer>::new();
+        let name =
+            follow_cnames("a0.local", browse_cnames(&cnames), &interface_name, &mut answers)
+                .unwrap();
+        assert_eq!(answers.len(), MAX_CNAME_CHAIN);
+        assert_eq!(name, format!("a{}.local", MAX_CNAME_CHAIN));
+    }
+
+    #[test]
+    fn address_records_are_decoded() {
+        let records = vec![
+            record(vec![192, 168, 1, 2]),
+            record("fe80::2".parse::<std::net::Ipv6Addr>().unwrap().octets().to_vec()),
+            record(vec![1, 2, 3]),
+        ];
+        let answers = address_answers("gandalf.local", records, &interface_name);
+        let lines: Vec<String> = answers.iter().map(Answer::to_line).collect();
+        assert_eq!(lines, vec![
+            "gandalf.local has address 192.168.1.2 on eth0 (IPv4)",
+            "gandalf.local has address fe80::2 on eth0 (IPv4)",
+        ]);
+        assert_eq!(answers[1].record_type, "AAAA");
+        assert_eq!(answers[0].to_record()[..3], [
+            Value::Text(String::from("gandalf.local")),
+            Value::Text(String::from("A")),
+            Value::Text(String::from("192.168.1.2")),
+        ]);
+    }
+
+    #[test]
+    fn unresolved_aliases_report_the_error() {
+        let status = AliasStatus::unresolved("a.local", &anyhow!("timeout"), 3000);
+        assert_eq!(status.status, "unresolved");
+        assert_eq!(status.to_line(7), "a.local  unresolved  timeout [3000 ms]");
+        assert_eq!(status.to_record(), vec![
+            Value::Text(String::from("a.local")),
+            Value::Text(String::from("unresolved")),
+            Value::Null,
+            Value::Null,
+            Value::Null,
+            Value::Number(3000),
+            Value::Text(String::from("timeout")),
+        ]);
+    }
+}
+
+// end
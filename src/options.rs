@@ -1,6 +1,18 @@
 #![warn(clippy::all)]
 
+use std::net::IpAddr;
+
+use structopt::clap::Shell;
 pub use structopt::StructOpt;
+use time::OffsetDateTime;
+
+use crate::completions::SHELLS;
+use crate::history::HISTORY_LIMIT;
+use crate::interchange::ExchangeFormat;
+use crate::{expiry, template};
+use crate::line::{is_valid_attribute, OWNER_ATTRIBUTE};
+use crate::output::OutputFormat;
+use crate::policy::POLICY_FILE;
 
 #[derive(Debug, StructOpt)]
 #[structopt(name = "avahi-alias", about = "Maintain /etc/avahi/avahi-aliases")]
@@ -11,6 +23,23 @@ pub struct CommandOpts {
 
     #[structopt(flatten)]
     pub common: CommonOpts, // cov(skip)
+
+    /// Shows the changes to the avahi-aliases file (as a unified diff) without making them
+    #[structopt(long = "dry-run", global = true)]
+    pub dry_run: bool, // cov(skip)
+
+    /// Shows the changes made to the avahi-aliases file (as a unified diff)
+    #[structopt(long = "diff", global = true)]
+    pub diff: bool, // cov(skip)
+
+    /// Sets the directory where previous versions of the avahi-aliases file are saved
+    /// [default: /var/lib/avahi-aliases/history, if it exists]
+    #[structopt(long = "history-dir", global = true)]
+    pub history_dir: Option<String>,
+
+    /// Sets how many previous versions of the avahi-aliases file are saved (0 saves none)
+    #[structopt(long = "history-limit", global = true, default_value = HISTORY_LIMIT)]
+    pub history_limit: usize,
 }
 
 #[derive(Debug, StructOpt)]
@@ -27,6 +56,59 @@ pub struct DaemonOpts {
     /// Log to syslog (vice console)
     #[structopt(long = "syslog")]
     pub syslog: bool, // cov(skip)
+
+    /// Defines an alias template variable (e.g., `--define site=east` for `{site}`)
+    #[structopt(
+        short = "D",
+        long = "define",
+        name = "NAME=VALUE",
+        number_of_values = 1,
+        parse(try_from_str = parse_definition)
+    )]
+    pub definitions: Vec<(String, String)>,
+}
+
+/// Parse a template variable definition (`NAME=VALUE`).
+fn parse_definition(definition: &str) -> Result<(String, String), String> {
+    let (name, value) = definition.split_once('=').ok_or_else(|| {
+        format!(r#"invalid definition "{}" (expected NAME=VALUE)"#, definition)
+    })?;
+    if !template::is_valid_template(&format!("{{{}}}.local", name)) {
+        return Err(format!(r#"invalid variable name "{}""#, name));
+    }
+    if template::host_variables("").contains_key(name) {
+        return Err(format!(r#"variable "{}" cannot be redefined"#, name));
+    }
+    Ok((name.to_owned(), value.to_owned()))
+}
+
+/// Parse an alias comment (`add --comment`), which must be on one line.
+fn parse_comment(comment: &str) -> Result<String, String> {
+    match comment.contains(['\n', '\r']) {
+        true => Err(String::from("comments must be on one line")),
+        false => Ok(comment.trim().to_owned()),
+    }
+}
+
+/// Parse an alias owner (`add --owner`), which is stored as an `owner=...` attribute.
+fn parse_owner(owner: &str) -> Result<String, String> {
+    match is_valid_attribute(OWNER_ATTRIBUTE, owner) {
+        true => Ok(owner.to_owned()),
+        false => Err(format!(r#"invalid owner "{}" (spaces and '#' are not allowed)"#, owner)),
+    }
+}
+
+/// Parse a version number (`undo`), which counts back from 1.
+fn parse_version(version: &str) -> Result<usize, String> {
+    match version.parse::<usize>() {
+        Ok(version) if version > 0 => Ok(version),
+        _ => Err(format!(r#"invalid version "{}" (expected 1 or more)"#, version)),
+    }
+}
+
+/// Parse an alias expiry (`add --expires`) relative to the current time.
+fn parse_expires(expires: &str) -> Result<OffsetDateTime, String> {
+    expiry::parse_expiry(expires, OffsetDateTime::now_utc())
 }
 
 #[derive(Debug, StructOpt)]
@@ -48,30 +130,309 @@ pub struct CommonOpts {
         default_value = "/etc/avahi/avahi-aliases"
     )]
     pub file: String,
+
+    /// Sets the policy file restricting the aliases (ignored if it does not exist)
+    #[structopt(long = "policy", global = true, name = "POLICY-FILE", default_value = POLICY_FILE)]
+    pub policy: String,
 }
 
 #[derive(Debug, StructOpt)]
 pub enum Command {
     #[structopt(about = "Add Aliases")]
     Add {
-        /// Aliases to add
-        #[structopt(name = "ALIAS", required = true)]
+        /// Aliases to add (`-` reads aliases from standard input)
+        #[structopt(name = "ALIAS", required_unless = "from")]
         aliases: Vec<String>,
+
+        /// Read aliases to add from a file, one per line
+        #[structopt(long = "from", name = "from")]
+        from: Option<String>,
+
+        /// Add the aliases to a group (created if necessary)
+        #[structopt(short = "g", long = "group")]
+        group: Option<String>,
+
+        /// Add aliases already claimed by another host on the network (with a warning)
+        #[structopt(long = "allow-conflict")]
+        allow_conflict: bool, // cov(skip)
+
+        /// Add the aliases without asking the network whether they are claimed (e.g., to add
+        /// many aliases quickly)
+        #[structopt(long = "no-conflict-check", conflicts_with = "allow-conflict")]
+        no_conflict_check: bool, // cov(skip)
+
+        /// Comment on the aliases (e.g., what they are for)
+        #[structopt(long = "comment", parse(try_from_str = parse_comment))]
+        comment: Option<String>,
+
+        /// Owner of the aliases (e.g., a user or team name without spaces)
+        #[structopt(long = "owner", parse(try_from_str = parse_owner))]
+        owner: Option<String>,
+
+        /// When the aliases expire: a duration (e.g., 2h or 1d) or an RFC 3339 timestamp
+        #[structopt(long = "expires", name = "WHEN", parse(try_from_str = parse_expires))]
+        expires: Option<OffsetDateTime>,
+
+        /// Create the aliases file if it does not exist
+        #[structopt(long = "create")]
+        create: bool, // cov(skip)
+    },
+
+    #[structopt(about = "Create a new Aliases file")]
+    Init {
+        /// Overwrite an existing file
+        #[structopt(long = "force")]
+        force: bool, // cov(skip)
     },
 
     #[structopt(about = "Remove Aliases")]
     Remove {
-        /// Aliases to remove
-        #[structopt(name = "ALIAS", required_unless = "force")]
+        /// Aliases to remove (`-` reads aliases from standard input)
+        #[structopt(name = "ALIAS", required_unless_one = &["force", "from", "glob", "regex"])]
         aliases: Vec<String>,
 
+        /// Read aliases to remove from a file, one per line
+        #[structopt(long = "from", name = "from")]
+        from: Option<String>,
+
+        /// Remove the aliases matching a glob pattern (e.g., `pr-*.local`)
+        #[structopt(long = "glob", name = "glob", conflicts_with_all = &["ALIAS", "from", "regex"])]
+        glob: Option<String>,
+
+        /// Remove the aliases matching a regular expression (e.g., `^pr-[0-9]+\.local$`)
+        #[structopt(long = "regex", name = "regex", conflicts_with_all = &["ALIAS", "from"])]
+        regex: Option<String>,
+
+        /// Remove the aliases matching --glob or --regex without asking for confirmation
+        #[structopt(short = "y", long = "yes")]
+        yes: bool, // cov(skip)
+
         /// Force removal of invalid aliases
         #[structopt(long = "force", global = true)]
         force: bool, // cov(skip)
     },
 
     #[structopt(about = "List existing Aliases")]
+    List {
+        /// List the aliases for every host (ignore host selectors)
+        #[structopt(long = "all-hosts")]
+        all_hosts: bool, // cov(skip)
+
+        /// Output format (text, json, csv, or tsv)
+        #[structopt(long = "format", default_value = "text")]
+        format: OutputFormat,
+
+        /// List only valid aliases
+        #[structopt(long = "valid-only", conflicts_with = "invalid-only")]
+        valid_only: bool, // cov(skip)
+
+        /// List only invalid aliases
+        #[structopt(long = "invalid-only")]
+        invalid_only: bool, // cov(skip)
+
+        /// List only the aliases with this owner
+        #[structopt(long = "owner")]
+        owner: Option<String>,
+    },
+
+    #[structopt(about = "Disable Aliases (without removing them)")]
+    Disable {
+        /// Aliases to disable
+        #[structopt(name = "ALIAS", required = true)]
+        aliases: Vec<String>,
+    },
+
+    #[structopt(about = "Enable disabled Aliases")]
+    Enable {
+        /// Aliases to enable
+        #[structopt(name = "ALIAS", required = true)]
+        aliases: Vec<String>,
+    },
+
+    #[structopt(about = "Rename an Alias (in place, keeping its comment)")]
+    Rename {
+        /// Alias to rename
+        #[structopt(name = "OLD")]
+        old: String,
+
+        /// New name
+        #[structopt(name = "NEW")]
+        new: String,
+    },
+
+    #[structopt(about = "Publish Aliases until interrupted (without the Aliases file)")]
+    Publish {
+        /// Aliases to publish
+        #[structopt(name = "ALIAS", required = true)]
+        aliases: Vec<String>,
+    },
+
+    #[structopt(about = "Publish Aliases while a command runs")]
+    Run {
+        /// Alias to publish (may be repeated)
+        #[structopt(short = "a", long = "alias", required = true, number_of_values = 1)]
+        aliases: Vec<String>,
+
+        /// Command (and arguments) to run
+        #[structopt(name = "COMMAND", required = true, last = true)]
+        command: Vec<String>,
+    },
+
+    #[structopt(about = "Remove expired Aliases from the Aliases file")]
+    Prune {},
+
+    #[structopt(about = "List the saved versions of the Aliases file")]
+    History {
+        /// Output format (text, json, csv, or tsv)
+        #[structopt(long = "format", default_value = "text")]
+        format: OutputFormat,
+    },
+
+    #[structopt(about = "Restore a saved version of the Aliases file")]
+    Undo {
+        /// The version to restore (1 is the version before the last change; see history)
+        #[structopt(name = "VERSION", default_value = "1", parse(try_from_str = parse_version))]
+        version: usize,
+    },
+
+    #[structopt(about = "Format the Aliases file")]
+    Fmt {
+        /// Check the format (fail if the file is not formatted) without changing the file
+        #[structopt(long = "check")]
+        check: bool, // cov(skip)
+
+        /// Sort the aliases in each block of consecutive aliases
+        #[structopt(long = "sort")]
+        sort: bool, // cov(skip)
+    },
+
+    #[structopt(about = "Make the Aliases match a desired set (add missing, remove extra)")]
+    Sync {
+        /// The desired aliases
+        #[structopt(name = "ALIAS", required_unless = "from")]
+        aliases: Vec<String>,
+
+        /// Read the desired aliases from a file, one per line (`-` for standard input)
+        #[structopt(long = "from", name = "from")]
+        from: Option<String>,
+
+        /// Keep aliases that are not in the desired set
+        #[structopt(long = "keep-extra")]
+        keep_extra: bool, // cov(skip)
+    },
+
+    #[structopt(about = "Edit the Aliases file (validated before it is saved)")]
+    Edit {},
+
+    #[structopt(about = "Show whether the Aliases are live on the network")]
+    Status {
+        /// Aliases to check (default: every alias for this host)
+        #[structopt(name = "ALIAS")]
+        aliases: Vec<String>,
+
+        /// Output format (text, json, csv, or tsv)
+        #[structopt(long = "format", default_value = "text")]
+        format: OutputFormat,
+    },
+
+    #[structopt(about = "Diagnose the environment (Avahi, name service, daemon, and file)")]
+    Doctor {
+        /// Output format (text, json, csv, or tsv)
+        #[structopt(long = "format", default_value = "text")]
+        format: OutputFormat,
+    },
+
+    #[structopt(about = "Resolve a name or address using Avahi (mDNS)")]
+    Resolve {
+        /// The name (e.g., an alias) or address to resolve
+        #[structopt(name = "NAME|ADDRESS")]
+        name: String,
+
+        /// Output format (text, json, csv, or tsv)
+        #[structopt(long = "format", default_value = "text")]
+        format: OutputFormat,
+    },
+
+    #[structopt(about = "Check Aliases files for problems (without changing them)")]
+    Check {
+        /// Aliases files to check (default: the Aliases file)
+        #[structopt(name = "FILE")]
+        files: Vec<String>,
+
+        /// Output format (text, json, csv, or tsv)
+        #[structopt(long = "format", default_value = "text")]
+        format: OutputFormat,
+    },
+
+    #[structopt(about = "Export Aliases (with the host's addresses) for other name services")]
+    Export {
+        /// Format (hosts, avahi-hosts, dnsmasq, bind-zone, or aliases)
+        #[structopt(long = "format", default_value = "hosts")]
+        format: ExchangeFormat,
+
+        /// The host name the aliases refer to (default: the host name from Avahi)
+        #[structopt(long = "target")]
+        target: Option<String>,
+
+        /// An address of the host (may be repeated; default: the addresses from Avahi)
+        #[structopt(long = "address", number_of_values = 1)]
+        addresses: Vec<IpAddr>,
+    },
+
+    #[structopt(about = "Import the .local names from other files as Aliases")]
+    Import {
+        /// Files to import (directories import each file in them)
+        #[structopt(name = "FILE", required = true)]
+        files: Vec<String>,
+
+        /// Format (hosts, avahi-hosts, dnsmasq, bind-zone, or aliases)
+        #[structopt(long = "format", default_value = "hosts")]
+        format: ExchangeFormat,
+
+        /// Add the aliases to a group (created if necessary)
+        #[structopt(short = "g", long = "group")]
+        group: Option<String>,
+    },
+
+    #[structopt(about = "Print a shell completion script (which also completes Alias names)")]
+    Completions {
+        /// The shell (bash, zsh, or fish)
+        #[structopt(name = "SHELL", possible_values = &SHELLS)]
+        shell: Shell,
+    },
+
+    /// List the Aliases a command applies to (for the completion scripts)
+    #[structopt(name = "__complete", setting = structopt::clap::AppSettings::Hidden)]
+    Complete {
+        #[structopt(name = "COMMAND")]
+        command: String,
+    },
+
+    #[structopt(about = "List, enable, or disable Alias groups")]
+    Group {
+        #[structopt(subcommand)]
+        cmd: GroupCommand, // cov(skip)
+    },
+}
+
+#[derive(Debug, StructOpt)]
+pub enum GroupCommand {
+    #[structopt(about = "List groups")]
     List {},
+
+    #[structopt(about = "Enable groups")]
+    Enable {
+        /// Groups to enable
+        #[structopt(name = "GROUP", required = true)]
+        groups: Vec<String>,
+    },
+
+    #[structopt(about = "Disable groups")]
+    Disable {
+        /// Groups to disable
+        #[structopt(name = "GROUP", required = true)]
+        groups: Vec<String>,
+    },
 }
 
 //**********************************************************************************************
@@ -111,6 +472,16 @@ mod tests {
         assert!(CommonOpts::from_iter(["", "--verbose"]).verbose);
     }
 
+    #[test]
+    fn dry_run_and_diff_flags_work() {
+        let opts = CommandOpts::from_iter(["", "add", "a1.local"]);
+        assert!(!opts.dry_run && !opts.diff);
+        let opts = CommandOpts::from_iter(["", "--dry-run", "add", "a1.local"]);
+        assert!(opts.dry_run && !opts.diff);
+        let opts = CommandOpts::from_iter(["", "remove", "a1.local", "--diff"]);
+        assert!(!opts.dry_run && opts.diff);
+    }
+
     #[test]
     fn empty_common_options_work_for_command() {
         for cmd in ["add", "list", "remove"] {
@@ -208,6 +579,83 @@ mod tests {
         }
     }
 
+    #[test]
+    fn add_command_group_option_works() {
+        match CommandOpts::from_iter(["", "add", "a1.local"]).cmd {
+            Command::Add { group, .. } => assert!(group.is_none()),
+            cmd => panic!("unexpected command: {:?}", cmd),
+        }
+        match CommandOpts::from_iter(["", "add", "--group", "web", "a1.local"]).cmd {
+            Command::Add { group, .. } => assert_eq!(group.as_deref(), Some("web")),
+            cmd => panic!("unexpected command: {:?}", cmd),
+        }
+    }
+
+    #[test]
+    fn add_command_allow_conflict_flag_works() {
+        assert!(matches!(CommandOpts::from_iter(["", "add", "a1.local"]).cmd, Command::Add {
+            allow_conflict: false,
+            ..
+        }));
+        assert!(matches!(
+            CommandOpts::from_iter(["", "add", "--allow-conflict", "a1.local"]).cmd,
+            Command::Add { allow_conflict: true, .. }
+        ));
+        assert!(matches!(
+            CommandOpts::from_iter(["", "add", "--no-conflict-check", "a1.local"]).cmd,
+            Command::Add { no_conflict_check: true, allow_conflict: false, .. }
+        ));
+        let args = ["", "add", "--no-conflict-check", "--allow-conflict", "a1.local"];
+        assert!(CommandOpts::from_iter_safe(args).is_err());
+    }
+
+    #[test]
+    fn add_command_comment_and_owner_options_work() {
+        let args = ["", "add", "--comment", " Grafana for X ", "--owner", "team-x", "a.local"];
+        match CommandOpts::from_iter(args).cmd {
+            Command::Add { comment, owner, .. } => {
+                assert_eq!(comment.as_deref(), Some("Grafana for X"));
+                assert_eq!(owner.as_deref(), Some("team-x"));
+            },
+            cmd => panic!("unexpected command: {:?}", cmd),
+        }
+        for args in [
+            ["", "add", "--owner", "team x", "a.local"],
+            ["", "add", "--owner", "a#b", "a.local"],
+            ["", "add", "--comment", "a\nb", "a.local"],
+        ] {
+            assert!(CommandOpts::from_iter_safe(args).is_err());
+        }
+    }
+
+    #[test]
+    fn add_command_expires_option_works() {
+        match CommandOpts::from_iter(["", "add", "--expires", "2h", "a.local"]).cmd {
+            Command::Add { expires: Some(expires), .. } => {
+                let remaining = expires - OffsetDateTime::now_utc();
+                assert!(remaining > time::Duration::minutes(119));
+                assert!(remaining <= time::Duration::hours(2));
+            },
+            cmd => panic!("unexpected command: {:?}", cmd),
+        }
+        for expires in ["soon", "2000-01-01T00:00:00Z"] {
+            let args = ["", "add", "--expires", expires, "a.local"];
+            assert!(CommandOpts::from_iter_safe(args).is_err());
+        }
+    }
+
+    #[test]
+    fn add_command_create_flag_works() {
+        assert!(matches!(CommandOpts::from_iter(["", "add", "a.local"]).cmd, Command::Add {
+            create: false,
+            ..
+        }));
+        assert!(matches!(
+            CommandOpts::from_iter(["", "add", "--create", "a.local"]).cmd,
+            Command::Add { create: true, .. }
+        ));
+    }
+
     #[test]
     fn add_command_requires_at_least_one_alias() {
         let opts = CommandOpts::from_iter_safe(["", "add"]);
@@ -218,12 +666,92 @@ mod tests {
         assert!(opts.unwrap_err().message.contains("<ALIAS>"));
     }
 
+    #[test]
+    fn add_and_remove_from_options_work() {
+        for cmd in ["add", "remove"] {
+            match CommandOpts::from_iter(["", cmd, "--from", "aliases.txt"]).cmd {
+                Command::Add { aliases, from, .. } | Command::Remove { aliases, from, .. } => {
+                    assert!(aliases.is_empty());
+                    assert_eq!(from.as_deref(), Some("aliases.txt"));
+                },
+                cmd => panic!("unexpected command: {:?}", cmd),
+            }
+            match CommandOpts::from_iter(["", cmd, "-"]).cmd {
+                Command::Add { aliases, from, .. } | Command::Remove { aliases, from, .. } => {
+                    assert_eq!(aliases, vec!["-"]);
+                    assert!(from.is_none());
+                },
+                cmd => panic!("unexpected command: {:?}", cmd),
+            }
+        }
+        assert!(CommandOpts::from_iter_safe(["", "remove"]).is_err());
+    }
+
+    //******************************************************************************************
+    // Init Command
+
+    #[test]
+    fn init_command_yields_force() {
+        assert!(matches!(CommandOpts::from_iter(["", "init"]).cmd, Command::Init {
+            force: false
+        }));
+        let opts = CommandOpts::from_iter(["", "init", "--force", "--file", "aliases"]);
+        assert!(matches!(opts.cmd, Command::Init { force: true }));
+        assert_eq!(opts.common.file, "aliases");
+    }
+
     //******************************************************************************************
     // List Command
 
     #[test]
     fn list_command_yields_list_command_opts() {
-        assert!(matches!(CommandOpts::from_iter(["", "list"]).cmd, Command::List { .. }));
+        assert!(matches!(CommandOpts::from_iter(["", "list"]).cmd, Command::List {
+            all_hosts: false,
+            format: OutputFormat::Text,
+            valid_only: false,
+            invalid_only: false,
+            owner: None,
+        }));
+    }
+
+    #[test]
+    fn list_all_hosts_flag_works() {
+        assert!(matches!(
+            CommandOpts::from_iter(["", "list", "--all-hosts"]).cmd,
+            Command::List { all_hosts: true, .. }
+        ));
+    }
+
+    #[test]
+    fn list_format_option_works() {
+        for (name, format) in [("json", OutputFormat::Json), ("csv", OutputFormat::Csv)] {
+            match CommandOpts::from_iter(["", "list", "--format", name]).cmd {
+                Command::List { format: f, .. } => assert_eq!(f, format),
+                cmd => panic!("unexpected command: {:?}", cmd),
+            }
+        }
+    }
+
+    #[test]
+    fn list_validity_filters_work() {
+        assert!(matches!(
+            CommandOpts::from_iter(["", "list", "--valid-only"]).cmd,
+            Command::List { valid_only: true, invalid_only: false, .. }
+        ));
+        assert!(matches!(
+            CommandOpts::from_iter(["", "list", "--invalid-only"]).cmd,
+            Command::List { valid_only: false, invalid_only: true, .. }
+        ));
+        let args = ["", "list", "--valid-only", "--invalid-only"];
+        assert!(CommandOpts::from_iter_safe(args).is_err());
+    }
+
+    #[test]
+    fn list_owner_filter_works() {
+        match CommandOpts::from_iter(["", "list", "--owner", "team-x"]).cmd {
+            Command::List { owner, .. } => assert_eq!(owner.as_deref(), Some("team-x")),
+            cmd => panic!("unexpected command: {:?}", cmd),
+        }
     }
 
     //******************************************************************************************
@@ -237,6 +765,402 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn remove_command_accepts_patterns() {
+        match CommandOpts::from_iter(["", "remove", "--glob", "pr-*.local", "--yes"]).cmd {
+            Command::Remove { aliases, glob, regex, yes, .. } => {
+                assert!(aliases.is_empty());
+                assert_eq!(glob.as_deref(), Some("pr-*.local"));
+                assert_eq!(regex, None);
+                assert!(yes);
+            },
+            cmd => panic!("unexpected command: {:?}", cmd),
+        }
+        match CommandOpts::from_iter(["", "remove", "--regex", "^pr-"]).cmd {
+            Command::Remove { regex, yes, .. } => {
+                assert_eq!(regex.as_deref(), Some("^pr-"));
+                assert!(!yes);
+            },
+            cmd => panic!("unexpected command: {:?}", cmd),
+        }
+        for args in [
+            vec!["", "remove", "--glob", "a*", "b.local"],
+            vec!["", "remove", "--glob", "a*", "--regex", "a"],
+            vec!["", "remove", "--regex", "a", "--from", "aliases.txt"],
+        ] {
+            assert!(CommandOpts::from_iter_safe(args).is_err());
+        }
+    }
+
+    //******************************************************************************************
+    // Publish and Run Commands
+
+    #[test]
+    fn publish_command_yields_aliases() {
+        match CommandOpts::from_iter(["", "publish", "a.local", "b.local"]).cmd {
+            Command::Publish { aliases } => assert_eq!(aliases, vec!["a.local", "b.local"]),
+            cmd => panic!("unexpected command: {:?}", cmd),
+        }
+        assert!(CommandOpts::from_iter_safe(["", "publish"]).is_err());
+    }
+
+    #[test]
+    fn run_command_yields_aliases_and_command() {
+        let args =
+            ["", "run", "-a", "a.local", "--alias", "b.local", "--", "npm", "start", "-v"];
+        match CommandOpts::from_iter(args).cmd {
+            Command::Run { aliases, command } => {
+                assert_eq!(aliases, vec!["a.local", "b.local"]);
+                assert_eq!(command, vec!["npm", "start", "-v"]);
+            },
+            cmd => panic!("unexpected command: {:?}", cmd),
+        }
+        assert!(CommandOpts::from_iter_safe(["", "run", "--", "npm"]).is_err());
+        assert!(CommandOpts::from_iter_safe(["", "run", "-a", "a.local"]).is_err());
+    }
+
+    //******************************************************************************************
+    // Prune Command
+
+    #[test]
+    fn prune_command_yields_prune_command_opts() {
+        assert!(matches!(CommandOpts::from_iter(["", "prune"]).cmd, Command::Prune {}));
+    }
+
+    //******************************************************************************************
+    // Disable and Enable Commands
+
+    #[test]
+    fn disable_command_yields_disable_command_opts() {
+        match CommandOpts::from_iter(["", "disable", "a1.local", "a2.local"]).cmd {
+            Command::Disable { aliases } => assert_eq!(aliases, vec!["a1.local", "a2.local"]),
+            cmd => panic!("unexpected command: {:?}", cmd),
+        }
+    }
+
+    #[test]
+    fn rename_command_yields_rename_command_opts() {
+        match CommandOpts::from_iter(["", "rename", "a.local", "b.local"]).cmd {
+            Command::Rename { old, new } => {
+                assert_eq!((old, new), ("a.local".into(), "b.local".into()))
+            },
+            cmd => panic!("unexpected command: {:?}", cmd),
+        }
+        assert!(CommandOpts::from_iter_safe(["", "rename", "a.local"]).is_err());
+    }
+
+    #[test]
+    fn enable_command_yields_enable_command_opts() {
+        match CommandOpts::from_iter(["", "enable", "a1.local"]).cmd {
+            Command::Enable { aliases } => assert_eq!(aliases, vec!["a1.local"]),
+            cmd => panic!("unexpected command: {:?}", cmd),
+        }
+    }
+
+    #[test]
+    fn disable_and_enable_require_at_least_one_alias() {
+        for cmd in ["disable", "enable"] {
+            let opts = CommandOpts::from_iter_safe(["", cmd]);
+            assert!(opts.unwrap_err().message.contains("<ALIAS>"));
+        }
+    }
+
+    //******************************************************************************************
+    // Fmt Command
+
+    #[test]
+    fn fmt_command_yields_fmt_command_opts() {
+        assert!(matches!(CommandOpts::from_iter(["", "fmt"]).cmd, Command::Fmt {
+            check: false,
+            sort: false
+        }));
+        assert!(matches!(
+            CommandOpts::from_iter(["", "fmt", "--check", "--sort"]).cmd,
+            Command::Fmt { check: true, sort: true }
+        ));
+    }
+
+    //******************************************************************************************
+    // Sync Command
+
+    #[test]
+    fn sync_command_yields_aliases_and_options() {
+        match CommandOpts::from_iter(["", "sync", "a1.local", "a2.local"]).cmd {
+            Command::Sync { aliases, from, keep_extra } => {
+                assert_eq!(aliases, vec!["a1.local", "a2.local"]);
+                assert!(from.is_none());
+                assert!(!keep_extra);
+            },
+            cmd => panic!("unexpected command: {:?}", cmd),
+        }
+        match CommandOpts::from_iter(["", "sync", "--from", "-", "--keep-extra"]).cmd {
+            Command::Sync { aliases, from, keep_extra } => {
+                assert!(aliases.is_empty());
+                assert_eq!(from.as_deref(), Some("-"));
+                assert!(keep_extra);
+            },
+            cmd => panic!("unexpected command: {:?}", cmd),
+        }
+    }
+
+    #[test]
+    fn sync_command_requires_aliases_or_from() {
+        assert!(CommandOpts::from_iter_safe(["", "sync"]).is_err());
+    }
+
+    //******************************************************************************************
+    // Edit Command
+
+    #[test]
+    fn edit_command_yields_edit_command_opts() {
+        assert!(matches!(CommandOpts::from_iter(["", "edit"]).cmd, Command::Edit {}));
+        assert!(CommandOpts::from_iter_safe(["", "edit", "a.local"]).is_err());
+    }
+
+    //******************************************************************************************
+    // Doctor Command
+
+    #[test]
+    fn doctor_command_yields_format() {
+        assert!(matches!(CommandOpts::from_iter(["", "doctor"]).cmd, Command::Doctor {
+            format: OutputFormat::Text
+        }));
+        assert!(matches!(
+            CommandOpts::from_iter(["", "doctor", "--format", "csv"]).cmd,
+            Command::Doctor { format: OutputFormat::Csv }
+        ));
+    }
+
+    //******************************************************************************************
+    // Status Command
+
+    #[test]
+    fn status_command_yields_format() {
+        assert!(matches!(CommandOpts::from_iter(["", "status"]).cmd, Command::Status {
+            format: OutputFormat::Text,
+            ..
+        }));
+        assert!(matches!(
+            CommandOpts::from_iter(["", "status", "--format", "json"]).cmd,
+            Command::Status { format: OutputFormat::Json, .. }
+        ));
+        match CommandOpts::from_iter(["", "status", "a.local", "b.local"]).cmd {
+            Command::Status { aliases, .. } => assert_eq!(aliases, vec!["a.local", "b.local"]),
+            cmd => panic!("unexpected command: {:?}", cmd),
+        }
+    }
+
+    //******************************************************************************************
+    // Resolve Command
+
+    #[test]
+    fn resolve_command_yields_name_and_format() {
+        match CommandOpts::from_iter(["", "resolve", "a.local", "--format", "csv"]).cmd {
+            Command::Resolve { name, format } => {
+                assert_eq!(name, "a.local");
+                assert_eq!(format, OutputFormat::Csv);
+            },
+            cmd => panic!("unexpected command: {:?}", cmd),
+        }
+        assert!(CommandOpts::from_iter_safe(["", "resolve"]).is_err());
+    }
+
+    //******************************************************************************************
+    // Check Command
+
+    #[test]
+    fn check_command_yields_files_and_format() {
+        match CommandOpts::from_iter(["", "check"]).cmd {
+            Command::Check { files, format } => {
+                assert!(files.is_empty());
+                assert_eq!(format, OutputFormat::Text);
+            },
+            cmd => panic!("unexpected command: {:?}", cmd),
+        }
+        match CommandOpts::from_iter(["", "check", "--format", "json", "a", "b"]).cmd {
+            Command::Check { files, format } => {
+                assert_eq!(files, vec!["a", "b"]);
+                assert_eq!(format, OutputFormat::Json);
+            },
+            cmd => panic!("unexpected command: {:?}", cmd),
+        }
+    }
+
+    #[test]
+    fn check_command_rejects_unknown_formats() {
+        assert!(CommandOpts::from_iter_safe(["", "check", "--format", "xml"]).is_err());
+    }
+
+    //******************************************************************************************
+    // History and Undo Commands
+
+    #[test]
+    fn history_options_have_defaults() {
+        let opts = CommandOpts::from_iter(["", "history"]);
+        assert_eq!(opts.history_dir, None);
+        assert_eq!(opts.history_limit, 20);
+        assert!(matches!(opts.cmd, Command::History { format: OutputFormat::Text }));
+        let opts =
+            CommandOpts::from_iter(["", "list", "--history-dir", "/h", "--history-limit", "0"]);
+        assert_eq!(opts.history_dir.as_deref(), Some("/h"));
+        assert_eq!(opts.history_limit, 0);
+    }
+
+    #[test]
+    fn undo_command_yields_version() {
+        assert!(matches!(CommandOpts::from_iter(["", "undo"]).cmd, Command::Undo {
+            version: 1
+        }));
+        assert!(matches!(CommandOpts::from_iter(["", "undo", "3"]).cmd, Command::Undo {
+            version: 3
+        }));
+        for version in ["0", "-1", "x"] {
+            assert!(CommandOpts::from_iter_safe(["", "undo", version]).is_err());
+        }
+    }
+
+    //******************************************************************************************
+    // Export Command
+
+    #[test]
+    fn export_command_yields_format_target_and_addresses() {
+        match CommandOpts::from_iter(["", "export"]).cmd {
+            Command::Export { format, target, addresses } => {
+                assert_eq!(format, ExchangeFormat::Hosts);
+                assert_eq!(target, None);
+                assert!(addresses.is_empty());
+            },
+            cmd => panic!("unexpected command: {:?}", cmd),
+        }
+        let args = [
+            "",
+            "export",
+            "--format",
+            "bind-zone",
+            "--target",
+            "nas.local",
+            "--address",
+            "10.0.0.1",
+            "--address",
+            "fe80::1",
+        ];
+        match CommandOpts::from_iter(args).cmd {
+            Command::Export { format, target, addresses } => {
+                assert_eq!(format, ExchangeFormat::BindZone);
+                assert_eq!(target.as_deref(), Some("nas.local"));
+                assert_eq!(addresses, vec![
+                    "10.0.0.1".parse::<IpAddr>().unwrap(),
+                    "fe80::1".parse().unwrap()
+                ]);
+            },
+            cmd => panic!("unexpected command: {:?}", cmd),
+        }
+    }
+
+    #[test]
+    fn export_command_rejects_invalid_formats_and_addresses() {
+        assert!(CommandOpts::from_iter_safe(["", "export", "--format", "json"]).is_err());
+        assert!(CommandOpts::from_iter_safe(["", "export", "--address", "nas"]).is_err());
+    }
+
+    //******************************************************************************************
+    // Import Command
+
+    #[test]
+    fn import_command_yields_files_format_and_group() {
+        match CommandOpts::from_iter(["", "import", "/etc/hosts"]).cmd {
+            Command::Import { files, format, group } => {
+                assert_eq!(files, vec!["/etc/hosts"]);
+                assert_eq!(format, ExchangeFormat::Hosts);
+                assert_eq!(group, None);
+            },
+            cmd => panic!("unexpected command: {:?}", cmd),
+        }
+        let args = ["", "import", "--format", "aliases", "-g", "old", "a", "b"];
+        match CommandOpts::from_iter(args).cmd {
+            Command::Import { files, format, group } => {
+                assert_eq!(files, vec!["a", "b"]);
+                assert_eq!(format, ExchangeFormat::Aliases);
+                assert_eq!(group.as_deref(), Some("old"));
+            },
+            cmd => panic!("unexpected command: {:?}", cmd),
+        }
+    }
+
+    #[test]
+    fn import_command_requires_a_file() {
+        assert!(CommandOpts::from_iter_safe(["", "import"]).is_err());
+    }
+
+    //******************************************************************************************
+    // Completions and __complete Commands
+
+    #[test]
+    fn completions_command_yields_shell() {
+        for (name, shell) in [("bash", Shell::Bash), ("zsh", Shell::Zsh), ("fish", Shell::Fish)]
+        {
+            match CommandOpts::from_iter(["", "completions", name]).cmd {
+                Command::Completions { shell: parsed } => {
+                    assert_eq!(parsed.to_string(), shell.to_string())
+                },
+                cmd => panic!("unexpected command: {:?}", cmd),
+            }
+        }
+        for args in [&["", "completions"][..], &["", "completions", "powershell"]] {
+            assert!(CommandOpts::from_iter_safe(args).is_err());
+        }
+    }
+
+    #[test]
+    fn complete_command_is_hidden() {
+        match CommandOpts::from_iter(["", "-f", "x", "__complete", "remove"]).cmd {
+            Command::Complete { command } => assert_eq!(command, "remove"),
+            cmd => panic!("unexpected command: {:?}", cmd),
+        }
+        let mut help = Vec::<u8>::new();
+        CommandOpts::clap().write_long_help(&mut help).unwrap();
+        let help = String::from_utf8(help).unwrap();
+        assert!(help.contains("completions"));
+        assert!(!help.contains("__complete"));
+        // Mistyped commands are still rejected (with suggestions)
+        let error = CommandOpts::from_iter_safe(["", "lst"]).unwrap_err();
+        assert!(error.message.contains("list"), "{}", error.message);
+    }
+
+    //******************************************************************************************
+    // Group Command
+
+    #[test]
+    fn group_list_command_yields_group_list_opts() {
+        assert!(matches!(CommandOpts::from_iter(["", "group", "list"]).cmd, Command::Group {
+            cmd: GroupCommand::List {}
+        }));
+    }
+
+    #[test]
+    fn group_enable_and_disable_commands_yield_groups() {
+        match CommandOpts::from_iter(["", "group", "enable", "web", "db"]).cmd {
+            Command::Group { cmd: GroupCommand::Enable { groups } } => {
+                assert_eq!(groups, vec!["web", "db"])
+            },
+            cmd => panic!("unexpected command: {:?}", cmd),
+        }
+        match CommandOpts::from_iter(["", "group", "disable", "web"]).cmd {
+            Command::Group { cmd: GroupCommand::Disable { groups } } => {
+                assert_eq!(groups, vec!["web"])
+            },
+            cmd => panic!("unexpected command: {:?}", cmd),
+        }
+    }
+
+    #[test]
+    fn group_enable_and_disable_require_at_least_one_group() {
+        for cmd in ["enable", "disable"] {
+            let opts = CommandOpts::from_iter_safe(["", "group", cmd]);
+            assert!(opts.unwrap_err().message.contains("<GROUP>"));
+        }
+    }
+
     //******************************************************************************************
     // Command line aliases for add and remove subcommands
 
@@ -284,6 +1208,28 @@ fn daemon_empty_options_work() {
     assert!(!opts.syslog);
 }
 
+#[test]
+fn daemon_define_option_works() {
+    let opts = DaemonOpts::from_iter(["", "-D", "site=east", "--define", "rack=r1=x"]);
+    assert_eq!(opts.definitions, vec![
+        (String::from("site"), String::from("east")),
+        (String::from("rack"), String::from("r1=x")),
+    ]);
+    for definition in ["site", "=east", "si te=east", "hostname=x"] {
+        assert!(DaemonOpts::from_iter_safe(["", "--define", definition]).is_err());
+    }
+}
+
+#[test]
+fn policy_option_works() {
+    let opts = CommandOpts::from_iter(["", "list"]);
+    assert_eq!(opts.common.policy, "/etc/avahi/avahi-aliases.policy");
+    let opts = CommandOpts::from_iter(["", "add", "a.local", "--policy", "p"]);
+    assert_eq!(opts.common.policy, "p");
+    let opts = DaemonOpts::from_iter(["", "--policy", "p"]);
+    assert_eq!(opts.common.policy, "p");
+}
+
 #[test]
 fn daemon_long_options_work() {
     let opts = DaemonOpts::from_iter(["", "--poll", "10", "--syslog", "--file"]);
@@ -0,0 +1,281 @@
+//! Machine-readable output
+//!
+//! Output formats for commands that produce records (e.g., `avahi-alias list`), and the
+//! encoding of records in the machine-readable formats (JSON, CSV, and TSV).
+
+#![warn(clippy::all)]
+
+use std::fmt;
+use std::str::FromStr;
+
+/// Output format
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Human-readable text
+    Text,
+    /// JSON (an array of objects)
+    Json,
+    /// Comma-separated values (RFC 4180) with a header
+    Csv,
+    /// Tab-separated values with a header
+    Tsv,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            "csv" => Ok(OutputFormat::Csv),
+            "tsv" => Ok(OutputFormat::Tsv),
+            _ => Err(format!(r#"invalid format "{}""#, format)),
+        }
+    }
+}
+
+impl fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            OutputFormat::Text => "text",
+            OutputFormat::Json => "json",
+            OutputFormat::Csv => "csv",
+            OutputFormat::Tsv => "tsv",
+        })
+    }
+}
+
+/// A record field value
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Number(usize),
+    Text(String),
+}
+
+impl Value {
+    /// Create a text value, or `Null` if there is no text.
+    pub fn optional_text(text: Option<&str>) -> Self {
+        text.map_or(Value::Null, |text| Value::Text(text.to_owned()))
+    }
+
+    fn to_json(&self) -> String {
+        match self {
+            Value::Null => String::from("null"),
+            Value::Bool(value) => value.to_string(),
+            Value::Number(value) => value.to_string(),
+            Value::Text(text) => json_string(text),
+        }
+    }
+
+    /// The value as text (`Null` is empty)
+    fn to_text(&self) -> String {
+        match self {
+            Value::Null => String::new(),
+            Value::Bool(value) => value.to_string(),
+            Value::Number(value) => value.to_string(),
+            Value::Text(text) => text.clone(),
+        }
+    }
+}
+
+/// Encode records in a machine-readable format. Each record has one value per column.
+/// JSON records are objects keyed by column name; CSV and TSV have a header line. (`Text`
+/// is encoded as TSV without the header.)
+pub fn encode_records(
+    format: OutputFormat, columns: &[&str], records: &[Vec<Value>],
+) -> String {
+    let rows = |encode: fn(&str) -> String, separator: &str| -> Vec<String> {
+        records
+            .iter()
+            .map(|record| {
+                let fields: Vec<String> = record.iter().map(|v| encode(&v.to_text())).collect();
+                fields.join(separator)
+            })
+            .collect()
+    };
+    let with_header = |header: String, rows: Vec<String>| -> String {
+        std::iter::once(header).chain(rows).map(|row| row + "\n").collect()
+    };
+    match format {
+        OutputFormat::Json => {
+            let objects: Vec<String> = records
+                .iter()
+                .map(|record| {
+                    let fields: Vec<(&str, String)> = columns
+                        .iter()
+                        .copied()
+                        .zip(record.iter().map(Value::to_json))
+                        .collect();
+                    json_object(&fields)
+                })
+                .collect();
+            json_array(&objects) + "\n"
+        },
+        OutputFormat::Csv => {
+            let header: Vec<String> = columns.iter().map(|c| csv_field(c)).collect();
+            with_header(header.join(","), rows(csv_field, ","))
+        },
+        OutputFormat::Tsv => {
+            let header: Vec<String> = columns.iter().map(|c| tsv_field(c)).collect();
+            with_header(header.join("\t"), rows(tsv_field, "\t"))
+        },
+        OutputFormat::Text => rows(tsv_field, "\t").into_iter().map(|row| row + "\n").collect(),
+    }
+}
+
+/// Encode a CSV field, quoting it if necessary (RFC 4180).
+pub fn csv_field(text: &str) -> String {
+    match text.contains([',', '"', '\r', '\n']) {
+        true => format!(r#""{}""#, text.replace('"', r#""""#)),
+        false => text.to_owned(),
+    }
+}
+
+/// Encode a TSV field, escaping tabs, line breaks, and backslashes.
+pub fn tsv_field(text: &str) -> String {
+    text.replace('\\', r#"\\"#)
+        .replace('\t', r#"\t"#)
+        .replace('\n', r#"\n"#)
+        .replace('\r', r#"\r"#)
+}
+
+/// Encode a string as a JSON string (including the quotes).
+///
+/// # Examples
+///
+/// ```
+/// use avahi_aliases::output::json_string;
+///
+/// assert_eq!(json_string(r#"say "hi""#), r#""say \"hi\"""#);
+/// ```
+pub fn json_string(text: &str) -> String {
+    let mut json = String::with_capacity(text.len() + 2);
+    json.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => json.push_str(r#"\""#),
+            '\\' => json.push_str(r#"\\"#),
+            '\n' => json.push_str(r#"\n"#),
+            '\r' => json.push_str(r#"\r"#),
+            '\t' => json.push_str(r#"\t"#),
+            c if (c as u32) < 0x20 => json.push_str(&format!(r#"\u{:04x}"#, c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+/// Encode `(name, value)` pairs as a JSON object. The values must already be JSON encoded.
+pub fn json_object(fields: &[(&str, String)]) -> String {
+    let fields: Vec<String> =
+        fields.iter().map(|(name, value)| format!("{}:{}", json_string(name), value)).collect();
+    format!("{{{}}}", fields.join(","))
+}
+
+/// Encode JSON values (e.g., objects) as a JSON array, one value per line.
+pub fn json_array(values: &[String]) -> String {
+    match values.is_empty() {
+        true => String::from("[]"),
+        false => format!("[\n  {}\n]", values.join(",\n  ")),
+    }
+}
+
+//**********************************************************************************************
+// Unit tests
+//**********************************************************************************************
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn output_format_parses_and_displays() {
+        let formats = [
+            ("text", OutputFormat::Text),
+            ("json", OutputFormat::Json),
+            ("csv", OutputFormat::Csv),
+            ("tsv", OutputFormat::Tsv),
+        ];
+        for (text, format) in formats {
+            assert_eq!(text.parse::<OutputFormat>().unwrap(), format);
+            assert_eq!(format.to_string(), text);
+        }
+        assert!("xml".parse::<OutputFormat>().is_err());
+    }
+
+    #[test]
+    fn json_string_escapes_special_characters() {
+        let data = [
+            ("a.local", r#""a.local""#),
+            (r#"a "b" c"#, r#""a \"b\" c""#),
+            (r#"a\b"#, r#""a\\b""#),
+            ("a\tb\nc\r", r#""a\tb\nc\r""#),
+            ("\u{1}", r#""\u0001""#),
+            ("é", r#""é""#),
+        ];
+        for (text, json) in data {
+            assert_eq!(json_string(text), json);
+        }
+    }
+
+    #[test]
+    fn json_object_and_array_encode_correctly() {
+        let object = json_object(&[("a", json_string("x")), ("b", String::from("1"))]);
+        assert_eq!(object, r#"{"a":"x","b":1}"#);
+        assert_eq!(json_array(&[]), "[]");
+        assert_eq!(
+            json_array(&[object.clone(), object]),
+            format!("[\n  {},\n  {}\n]", r#"{"a":"x","b":1}"#, r#"{"a":"x","b":1}"#)
+        );
+    }
+
+    #[test]
+    fn csv_and_tsv_fields_are_escaped() {
+        assert_eq!(csv_field("a.local"), "a.local");
+        assert_eq!(csv_field("a, b"), r#""a, b""#);
+        assert_eq!(csv_field(r#"say "hi""#), r#""say ""hi""""#);
+        assert_eq!(tsv_field("a.local"), "a.local");
+        assert_eq!(tsv_field("a\tb\nc\\"), r#"a\tb\nc\\"#);
+    }
+
+    fn records() -> Vec<Vec<Value>> {
+        vec![
+            vec![Value::Text(String::from("a.local")), Value::Number(1), Value::Bool(true)],
+            vec![Value::Text(String::from("b, c")), Value::Number(2), Value::Null],
+        ]
+    }
+
+    #[test]
+    fn records_are_encoded_in_each_format() {
+        let columns = ["alias", "line", "valid"];
+        assert_eq!(
+            encode_records(OutputFormat::Json, &columns, &records()),
+            concat!(
+                "[\n",
+                r#"  {"alias":"a.local","line":1,"valid":true},"#,
+                "\n",
+                r#"  {"alias":"b, c","line":2,"valid":null}"#,
+                "\n]\n"
+            )
+        );
+        assert_eq!(
+            encode_records(OutputFormat::Csv, &columns, &records()),
+            "alias,line,valid\na.local,1,true\n\"b, c\",2,\n"
+        );
+        assert_eq!(
+            encode_records(OutputFormat::Tsv, &columns, &records()),
+            "alias\tline\tvalid\na.local\t1\ttrue\nb, c\t2\t\n"
+        );
+        assert_eq!(
+            encode_records(OutputFormat::Text, &columns, &records()),
+            "a.local\t1\ttrue\nb, c\t2\t\n"
+        );
+        assert_eq!(encode_records(OutputFormat::Csv, &columns, &[]), "alias,line,valid\n");
+    }
+}
+
+// end
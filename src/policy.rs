@@ -0,0 +1,321 @@
+//! Alias policy
+//!
+//! An optional policy file restricts which aliases may be added and published. Each line is a
+//! directive followed by its values (blank lines and comments, which start with `#` at the
+//! start of a line or after whitespace, are ignored):
+//!
+//! ```text
+//! # Names nobody may claim
+//! reserved router.local gateway.local
+//! # Aliases must match one of the allow expressions (if any) and none of the deny ones
+//! allow ^[a-z0-9-]+\.local$
+//! deny ^(router|gateway)
+//! # The maximum number of aliases published by a host
+//! max-aliases 50
+//! # Members of a Unix group must use one of the group's prefixes
+//! prefix lab lab-
+//! prefix devs dev- test-
+//! ```
+//!
+//! `avahi-alias` enforces the policy when aliases are added or renamed, and the daemon refuses
+//! to publish aliases that violate it. (The daemon cannot tell who added an alias, so prefixes
+//! are only enforced when aliases are added.)
+
+#![warn(clippy::all)]
+
+use std::ffi::{CStr, CString};
+use std::{env, fs, io};
+
+use anyhow::{anyhow, Context, Result};
+use regex::Regex;
+
+use crate::template;
+
+/// The default policy file
+pub const POLICY_FILE: &str = "/etc/avahi/avahi-aliases.policy";
+
+/// The most groups `user_groups` reports
+const MAX_GROUPS: usize = 256;
+
+/// Restrictions on the aliases that may be added and published
+#[derive(Debug, Default)]
+pub struct Policy {
+    reserved: Vec<String>,
+    allow: Vec<Regex>,
+    deny: Vec<Regex>,
+    max_aliases: Option<usize>,
+    /// Unix groups and the prefixes their members must use
+    prefixes: Vec<(String, Vec<String>)>,
+}
+
+impl Policy {
+    /// Load a policy file. A missing file is an empty policy (which allows every alias).
+    pub fn from_file(filename: &str) -> Result<Self> {
+        match fs::read_to_string(filename) {
+            Ok(text) => {
+                Self::parse(&text).map_err(|error| anyhow!(r#""{}" {}"#, filename, error))
+            },
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(error) => {
+                Err(error).with_context(|| format!(r#"could not read "{}""#, filename))
+            },
+        }
+    }
+
+    /// Parse a policy (see the module documentation).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use avahi_aliases::policy::Policy;
+    ///
+    /// let policy = Policy::parse("reserved router.local\ndeny ^gw").unwrap();
+    /// assert!(policy.violation("router.local").is_some());
+    /// assert!(policy.violation("gw-1.local").is_some());
+    /// assert!(policy.violation("web.local").is_none());
+    /// ```
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut policy = Self::default();
+        for (line, number) in text.lines().zip(1..) {
+            let line = strip_comment(line).trim();
+            let (directive, value) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+            let value = value.trim();
+            let error = |message: String| format!("line {}: {}", number, message);
+            let regex =
+                || Regex::new(value).map_err(|e| error(format!("invalid regex: {}", e)));
+            match directive {
+                "" => (),
+                _ if value.is_empty() => {
+                    return Err(error(format!(r#""{}" needs a value"#, directive)))
+                },
+                "reserved" => {
+                    policy.reserved.extend(value.split_whitespace().map(str::to_owned))
+                },
+                "allow" => policy.allow.push(regex()?),
+                "deny" => policy.deny.push(regex()?),
+                "max-aliases" => {
+                    let max = value
+                        .parse()
+                        .map_err(|_| error(format!(r#"invalid count "{}""#, value)))?;
+                    policy.max_aliases = Some(max);
+                },
+                "prefix" => {
+                    let mut words = value.split_whitespace().map(str::to_owned);
+                    let group = words.next().unwrap_or_default();
+                    let prefixes: Vec<String> = words.collect();
+                    if prefixes.is_empty() {
+                        return Err(error(format!(r#"no prefixes for group "{}""#, group)));
+                    }
+                    policy.prefixes.push((group, prefixes));
+                },
+                _ => return Err(error(format!(r#"unknown directive "{}""#, directive))),
+            }
+        }
+        Ok(policy)
+    }
+
+    /// Returns `true` if the policy allows every alias.
+    pub fn is_empty(&self) -> bool {
+        self.reserved.is_empty()
+            && self.allow.is_empty()
+            && self.deny.is_empty()
+            && self.max_aliases.is_none()
+            && self.prefixes.is_empty()
+    }
+
+    /// The maximum number of aliases a host may publish (if limited)
+    pub fn max_aliases(&self) -> Option<usize> { self.max_aliases }
+
+    /// Return why the policy does not allow an alias (reserved, not allowed, or denied), if
+    /// it does not. Templates are not checked (they are checked when expanded).
+    pub fn violation(&self, alias: &str) -> Option<String> {
+        if template::is_template(alias) {
+            return None;
+        }
+        if self.reserved.iter().any(|reserved| reserved.eq_ignore_ascii_case(alias)) {
+            return Some(String::from("the name is reserved"));
+        }
+        if !self.allow.is_empty() && !self.allow.iter().any(|allow| allow.is_match(alias)) {
+            return Some(String::from("the name does not match an allowed pattern"));
+        }
+        let deny = self.deny.iter().find(|deny| deny.is_match(alias))?;
+        Some(format!("the name matches the denied pattern {:?}", deny.as_str()))
+    }
+
+    /// Return why the policy does not allow a member of `groups` to add an alias (it does not
+    /// start with a prefix of one of the groups), if it does not.
+    pub fn prefix_violation(&self, alias: &str, groups: &[String]) -> Option<String> {
+        let prefixes: Vec<&str> = self
+            .prefixes
+            .iter()
+            .filter(|(group, _)| groups.contains(group))
+            .flat_map(|(_, prefixes)| prefixes.iter().map(String::as_str))
+            .collect();
+        match prefixes.is_empty() || prefixes.iter().any(|prefix| alias.starts_with(prefix)) {
+            true => None,
+            false => Some(format!("the name must start with {}", prefixes.join(" or "))),
+        }
+    }
+
+    /// Validate aliases added by a member of `groups`, which makes `alias_count` aliases in
+    /// all. Every violation is reported.
+    pub fn validate_aliases<T>(
+        &self, aliases: &[T], groups: &[String], alias_count: usize,
+    ) -> Result<()>
+    where
+        T: AsRef<str>, {
+        let mut violations = Vec::<String>::new();
+        for alias in aliases.iter().map(AsRef::as_ref) {
+            let violation =
+                self.violation(alias).or_else(|| self.prefix_violation(alias, groups));
+            if let Some(violation) = violation {
+                violations.push(format!("{:?} ({})", alias, violation));
+            }
+        }
+        if let Some(max_aliases) = self.max_aliases.filter(|max| alias_count > *max) {
+            violations.push(format!("more than {} aliases ({})", max_aliases, alias_count));
+        }
+        match violations.is_empty() {
+            true => Ok(()),
+            false => Err(anyhow!("not allowed by the policy: {}", violations.join(", "))),
+        }
+    }
+}
+
+/// Remove a comment from a line. `#` only starts a comment at the start of the line or after
+/// whitespace, so regexes may contain `#`.
+fn strip_comment(line: &str) -> &str {
+    let mut previous = ' ';
+    for (index, c) in line.char_indices() {
+        if c == '#' && previous.is_whitespace() {
+            return &line[..index];
+        }
+        previous = c;
+    }
+    line
+}
+
+/// The Unix groups of the user making a change (the user who ran `sudo`, if any)
+pub fn user_groups() -> Vec<String> {
+    // SAFETY: the passwd and group entries are copied before the next call that could
+    // overwrite them, and the group list buffer is as large as its reported length.
+    unsafe {
+        let passwd = match env::var("SUDO_USER").ok().and_then(|name| CString::new(name).ok()) {
+            Some(name) => libc::getpwnam(name.as_ptr()),
+            None => libc::getpwuid(libc::getuid()),
+        };
+        if passwd.is_null() {
+            return vec![];
+        }
+        let name = CStr::from_ptr((*passwd).pw_name).to_owned();
+        let gid = (*passwd).pw_gid;
+        let mut gids = vec![0 as libc::gid_t; MAX_GROUPS];
+        let mut count = MAX_GROUPS as libc::c_int;
+        if libc::getgrouplist(name.as_ptr(), gid, gids.as_mut_ptr(), &mut count) < 0 {
+            count = MAX_GROUPS as libc::c_int;
+        }
+        gids.truncate(count.max(0) as usize);
+        gids.into_iter()
+            .filter_map(|gid| {
+                let group = libc::getgrgid(gid);
+                (!group.is_null())
+                    .then(|| CStr::from_ptr((*group).gr_name).to_string_lossy().into_owned())
+            })
+            .collect()
+    }
+}
+
+//**********************************************************************************************
+// Unit tests
+//**********************************************************************************************
+
+#[cfg(test)]
+mod tests {
+    use super::{strip_comment, Policy};
+
+    const POLICY: &str = "
+        # Lab policy
+        reserved router.local Gateway.local
+        allow ^[a-z0-9-]+\\.local$
+        deny ^printer
+        max-aliases 3
+        prefix lab lab-
+        prefix devs dev- test-
+    ";
+
+    fn groups(groups: &[&str]) -> Vec<String> {
+        groups.iter().map(|group| group.to_string()).collect()
+    }
+
+    #[test]
+    fn empty_policies_allow_every_alias() {
+        for text in ["", "# Nothing\n\n"] {
+            let policy = Policy::parse(text).unwrap();
+            assert!(policy.is_empty());
+            assert!(policy
+                .validate_aliases(&["router.local"], &groups(&["lab"]), 1000)
+                .is_ok());
+        }
+        assert!(!Policy::parse(POLICY).unwrap().is_empty());
+    }
+
+    #[test]
+    fn reserved_denied_and_unallowed_names_are_violations() {
+        let policy = Policy::parse(POLICY).unwrap();
+        for alias in ["router.local", "gateway.local", "printer-1.local", "a.b.local"] {
+            assert!(policy.violation(alias).is_some(), "{}", alias);
+        }
+        assert_eq!(policy.violation("web.local"), None);
+        assert_eq!(policy.violation("{hostname}.local"), None);
+        assert_eq!(policy.max_aliases(), Some(3));
+    }
+
+    #[test]
+    fn group_members_must_use_a_group_prefix() {
+        let policy = Policy::parse(POLICY).unwrap();
+        assert!(policy.prefix_violation("web.local", &groups(&["lab"])).is_some());
+        assert_eq!(policy.prefix_violation("lab-web.local", &groups(&["lab"])), None);
+        assert_eq!(policy.prefix_violation("test-web.local", &groups(&["lab", "devs"])), None);
+        assert_eq!(policy.prefix_violation("web.local", &groups(&["staff"])), None);
+    }
+
+    #[test]
+    fn validate_aliases_reports_every_violation() {
+        let policy = Policy::parse(POLICY).unwrap();
+        assert!(policy.validate_aliases(&["lab-a.local"], &groups(&["lab"]), 3).is_ok());
+        let error = policy
+            .validate_aliases(&["router.local", "web.local"], &groups(&["lab"]), 4)
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains(r#""router.local" (the name is reserved)"#), "{}", error);
+        assert!(error.contains(r#""web.local" (the name must start with lab-)"#), "{}", error);
+        assert!(error.contains("more than 3 aliases (4)"), "{}", error);
+    }
+
+    #[test]
+    fn comments_start_at_the_line_start_or_after_whitespace() {
+        assert_eq!(strip_comment("# Comment"), "");
+        assert_eq!(strip_comment("deny ^gw # Gateways"), "deny ^gw ");
+        assert_eq!(strip_comment("allow ^[a-z#]+"), "allow ^[a-z#]+");
+        let policy = Policy::parse("allow ^[a-z#]+\\.local$ # Hash allowed").unwrap();
+        assert_eq!(policy.violation("a#b.local"), None);
+        assert!(policy.violation("a-b.local").is_some());
+    }
+
+    #[test]
+    fn invalid_policies_are_rejected_with_line_numbers() {
+        let data = [
+            ("colour red", "line 1: unknown directive"),
+            ("\nallow (", "line 2: invalid regex"),
+            ("max-aliases lots", r#"line 1: invalid count "lots""#),
+            ("prefix lab", r#"line 1: no prefixes for group "lab""#),
+            ("deny", r#"line 1: "deny" needs a value"#),
+        ];
+        for (text, message) in data {
+            let error = Policy::parse(text).unwrap_err();
+            assert!(error.starts_with(message), "{:?}: {}", text, error);
+        }
+    }
+}
+
+// end
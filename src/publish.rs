@@ -0,0 +1,107 @@
+//! Publish aliases through Avahi
+//!
+//! Aliases are published as CNAME records in Avahi entry groups. Avahi withdraws an entry
+//! group when it is freed or when the D-Bus connection that created it is closed (e.g., when
+//! the publishing process exits).
+
+#![warn(clippy::all)]
+
+use anyhow::Result;
+
+use crate::avahi_dbus::{
+    self, avahi, OrgFreedesktopAvahiEntryGroup, OrgFreedesktopAvahiServer,
+};
+
+/// An Avahi entry group publishing a set of aliases
+pub struct PublishedGroup<'c> {
+    /// A description of the group for messages (e.g., `group "web"`)
+    pub name: String,
+    proxy: avahi_dbus::DBusProxy<'static, 'c>,
+    state: i32,
+}
+
+impl PublishedGroup<'_> {
+    /// Returns `true` if Avahi withdrew the aliases (after a name collision or a failure).
+    pub fn is_withdrawn(&self) -> bool {
+        self.state == avahi::EntryGroupState::COLLISION as i32
+            || self.state == avahi::EntryGroupState::FAILURE as i32
+    }
+}
+
+/// Publish aliases (CNAME records with the encoded host name `rdata`) in a new entry group.
+pub fn publish_group<'c>(
+    avahi_server_proxy: &avahi_dbus::DBusProxy<'_, 'c>, name: &str, aliases: &[&str],
+    rdata: &[u8],
+) -> Result<PublishedGroup<'c>> {
+    let entry_group_path = avahi_server_proxy.entry_group_new()?;
+    let entry_group_proxy = avahi_server_proxy.connection.with_proxy(
+        avahi::AVAHI_DBUS_NAME,
+        entry_group_path,
+        crate::DEFAULT_TIMEOUT,
+    );
+    let published_group = PublishedGroup {
+        name: name.to_owned(),
+        proxy: entry_group_proxy,
+        state: avahi::EntryGroupState::UNCOMMITTED as i32,
+    };
+    for alias in aliases {
+        log::info!("Publishing alias {} ({})", alias, name);
+        let result = published_group.proxy.add_record(
+            avahi::Interface::UNSPECIFIED as i32,
+            avahi::Protocol::UNSPEC as i32,
+            0,
+            alias,
+            avahi::RecordClass::IN as u16,
+            avahi::RecordType::CNAME as u16,
+            60,
+            rdata.to_vec(),
+        );
+        if let Err(error) = result {
+            let _ = published_group.proxy.free();
+            return Err(error.into());
+        }
+    }
+    published_group.proxy.commit()?;
+    Ok(published_group)
+}
+
+/// Withdraw previously published aliases (by freeing their entry groups).
+pub fn withdraw_groups(published_groups: &[PublishedGroup<'_>]) {
+    for group in published_groups {
+        log::debug!("Withdrawing {}", group.name);
+        if let Err(error) = group.proxy.free() {
+            log::warn!("Could not withdraw {}: {}", group.name, error);
+        }
+    }
+}
+
+/// Log entry group state changes (e.g., collisions).
+pub fn check_groups(published_groups: &mut [PublishedGroup<'_>]) {
+    for group in published_groups.iter_mut() {
+        let state = match OrgFreedesktopAvahiEntryGroup::get_state(&group.proxy) {
+            Ok(state) => state,
+            Err(error) => {
+                log::warn!("Could not get the state of {}: {}", group.name, error);
+                continue;
+            },
+        };
+        if state == group.state {
+            continue;
+        }
+        match state {
+            s if s == avahi::EntryGroupState::ESTABLISHED as i32 => {
+                log::info!("Established {}", group.name)
+            },
+            s if s == avahi::EntryGroupState::COLLISION as i32 => {
+                log::warn!("Name collision in {}; its aliases were withdrawn", group.name)
+            },
+            s if s == avahi::EntryGroupState::FAILURE as i32 => {
+                log::warn!("Publishing {} failed; its aliases were withdrawn", group.name)
+            },
+            _ => log::debug!("{} state is {}", group.name, state),
+        }
+        group.state = state;
+    }
+}
+
+// end
@@ -0,0 +1,167 @@
+//! Alias templates
+//!
+//! An alias template contains placeholders, such as `{hostname}-grafana.local`, that are
+//! replaced by variable values when the aliases are published. The `hostname` and
+//! `shorthostname` variables are derived from the Avahi host name; other variables are
+//! defined on the daemon command line.
+
+#![warn(clippy::all)]
+
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
+use regex::Regex;
+use structopt::lazy_static::lazy_static;
+
+use crate::alias::is_valid_alias;
+
+/// Template variables (name → value)
+pub type Variables = HashMap<String, String>;
+
+lazy_static! {
+    static ref PLACEHOLDER_RE: Regex = Regex::new(r#"\{([A-Za-z_][A-Za-z0-9_]*)\}"#).unwrap();
+}
+
+/// Returns `true` if `text` looks like a template (i.e., contains a brace).
+pub fn is_template(text: &str) -> bool { text.contains(['{', '}']) }
+
+/// Determine the validity of an alias template.
+///
+/// A template is valid if every brace is part of a placeholder (`{name}`) and the template
+/// yields a valid alias when every placeholder is replaced by a letter.
+///
+/// # Examples
+///
+/// ```
+/// use avahi_aliases::template::is_valid_template;
+///
+/// is_valid_template("{hostname}-grafana.local"); // true
+/// is_valid_template("{hostname-grafana.local"); // false
+/// ```
+pub fn is_valid_template(template: &str) -> bool {
+    let sample = PLACEHOLDER_RE.replace_all(template, "x");
+    is_template(template) && !is_template(&sample) && is_valid_alias(&sample)
+}
+
+/// Replace the placeholders in `template` with the values in `variables`.
+///
+/// Returns an error if a placeholder names an undefined variable or a brace is not part of
+/// a placeholder. The result is not validated.
+///
+/// # Examples
+///
+/// ```
+/// use avahi_aliases::template::{expand, host_variables};
+///
+/// let variables = host_variables("monitor-01");
+/// let alias = expand("{hostname}-grafana.local", &variables).unwrap();
+/// assert_eq!(alias, "monitor-01-grafana.local");
+/// ```
+pub fn expand(template: &str, variables: &Variables) -> Result<String> {
+    if is_template(&PLACEHOLDER_RE.replace_all(template, "")) {
+        return Err(anyhow!(r#"malformed placeholder in "{}""#, template));
+    }
+    if let Some(captures) =
+        PLACEHOLDER_RE.captures_iter(template).find(|c| !variables.contains_key(&c[1]))
+    {
+        return Err(anyhow!(r#"undefined variable "{}" in "{}""#, &captures[1], template));
+    }
+    let expanded = PLACEHOLDER_RE
+        .replace_all(template, |c: &regex::Captures<'_>| variables[&c[1]].clone());
+    Ok(expanded.into_owned())
+}
+
+/// Returns `true` if every placeholder in `template` names a variable in `variables`.
+pub fn is_defined(template: &str, variables: &Variables) -> bool {
+    PLACEHOLDER_RE.captures_iter(template).all(|c| variables.contains_key(&c[1]))
+}
+
+/// Create the variables derived from the host name: `hostname` (the Avahi host name) and
+/// `shorthostname` (the host name up to the first dot). The values are lowercase.
+pub fn host_variables(host_name: &str) -> Variables {
+    let host_name = host_name.to_lowercase();
+    let short_host_name = host_name.split('.').next().unwrap_or_default().to_owned();
+    Variables::from([
+        (String::from("hostname"), host_name),
+        (String::from("shorthostname"), short_host_name),
+    ])
+}
+
+//**********************************************************************************************
+// Unit tests
+//**********************************************************************************************
+
+#[cfg(test)]
+mod tests {
+    use super::{expand, host_variables, is_defined, is_template, is_valid_template};
+
+    static VALID_TEMPLATES: [&str; 4] = [
+        "{hostname}.local",
+        "{hostname}-grafana.local",
+        "db-{shorthostname}.local",
+        "{site}-{hostname}.local",
+    ];
+    static INVALID_TEMPLATES: [&str; 6] = [
+        "a.local",
+        "{hostname.local",
+        "hostname}.local",
+        "{}.local",
+        "{host name}.local",
+        "{hostname}*.local",
+    ];
+
+    #[test]
+    fn is_defined_requires_every_variable() {
+        let variables = host_variables("db-01");
+        assert!(is_defined("{hostname}-{shorthostname}.local", &variables));
+        assert!(is_defined("a.local", &variables));
+        assert!(!is_defined("{site}-{hostname}.local", &variables));
+    }
+
+    #[test]
+    fn is_template_detects_braces() {
+        assert!(is_template("{hostname}.local"));
+        assert!(is_template("hostname}.local"));
+        assert!(!is_template("a.local"));
+    }
+
+    #[test]
+    fn is_valid_template_returns_true_for_valid_templates() {
+        VALID_TEMPLATES.iter().for_each(|t| assert!(is_valid_template(t), "{}", t))
+    }
+
+    #[test]
+    fn is_valid_template_returns_false_for_invalid_templates() {
+        INVALID_TEMPLATES.iter().for_each(|t| assert!(!is_valid_template(t), "{}", t))
+    }
+
+    #[test]
+    fn expand_replaces_placeholders() {
+        let mut variables = host_variables("Monitor-01.example");
+        variables.insert(String::from("site"), String::from("east"));
+        let data = [
+            ("{hostname}-grafana.local", "monitor-01.example-grafana.local"),
+            ("{shorthostname}-grafana.local", "monitor-01-grafana.local"),
+            ("{site}-{shorthostname}.local", "east-monitor-01.local"),
+            ("a.local", "a.local"),
+        ];
+        for (template, expected) in data {
+            assert_eq!(expand(template, &variables).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn expand_fails_for_undefined_variables() {
+        let error = expand("{site}-db.local", &host_variables("monitor-01")).unwrap_err();
+        assert!(error.to_string().contains(r#""site""#));
+    }
+
+    #[test]
+    fn expand_fails_for_malformed_placeholders() {
+        for template in ["{hostname.local", "{hostname}}.local", "{}.local"] {
+            assert!(expand(template, &host_variables("monitor-01")).is_err());
+        }
+    }
+}
+
+// end